@@ -1,6 +1,12 @@
-use std::sync::Arc;
+use std::{
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock},
+};
 
 use axum::{
+    extract::{DefaultBodyLimit, Request},
+    http::{header::CACHE_CONTROL, HeaderName},
+    middleware,
     routing::{get, post},
     Router,
 };
@@ -9,44 +15,85 @@ use axum_login::{
     tower_sessions::{cookie::time::Duration, Expiry, MemoryStore, SessionManagerLayer},
     AuthManagerLayerBuilder,
 };
-use comrak::{markdown_to_html_with_plugins, plugins::syntect, Options, Plugins};
-use minijinja::{context, Environment, Value};
+use chrono::{DateTime, Utc};
+use minijinja::{context, path_loader, value::Kwargs, Environment, ErrorKind, Value};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use object_store::ObjectStore;
 use tower_http::{
+    compression::CompressionLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     services::ServeDir,
+    set_header::SetResponseHeader,
     trace::{self, TraceLayer},
 };
 use tower_sessions::cookie::Key;
-use tracing::{info, Level};
+use tracing::{error, info, info_span, Level};
 
 use crate::{
+    assets::{embedded_template, embedded_template_names, handler_embedded_static},
+    cache::Validators,
     config::Config,
     error::Error,
     handlers::{
-        handler_404, handler_admin, handler_article, handler_articles, handler_change_pw_get,
+        handler_404, handler_admin, handler_article, handler_articles,
+        handler_autosave_discard_post, handler_autosave_post, handler_change_pw_get,
         handler_change_pw_post, handler_custom_page, handler_delete_post, handler_edit_article_get,
-        handler_edit_page_get, handler_edit_post, handler_feed, handler_home, handler_login_get,
-        handler_login_post, handler_logout, handler_page, handler_ping, handler_tag, handler_tags,
+        handler_edit_page_get, handler_edit_post, handler_error, handler_feed, handler_home,
+        handler_json_feed, handler_login_get, handler_login_post, handler_logout,
+        handler_media_delete, handler_media_library, handler_media_upload_post, handler_page,
+        handler_ping, handler_preview_post, handler_robots, handler_rss, handler_sitemap,
+        handler_tag, handler_tag_feed, handler_tags,
     },
+    i18n::I18n,
+    images::Images,
+    markdown::{excerpt, md_to_html},
+    media::{build_store, handler_media, MEDIA_URL_PREFIX},
+    micropub::{handler_micropub_get, handler_micropub_post},
     models::{create_tables_within_transaction, Article, Page, User},
+    og_image::{handler_og_image, OgImages},
+    theme::Theme,
+    utils::{format_datetime, DEFAULT_DATE_FORMAT},
+    xmlrpc::handler_xmlrpc,
 };
 
-const TEMPLATES_DIR: &str = "templates";
-const STATIC_DIR: &str = "static";
 // TODO: support specifying the config file path via command line argument.
 const CONFIG_FILE_PATH: &str = "config.toml";
+// The max body size of the XML-RPC requests, which is enough for a post or a base64-encoded image.
+const XMLRPC_BODY_LIMIT: usize = 4 * 1024 * 1024;
+// The extra body size allowed for the media uploads besides the max size of the file.
+const MEDIA_UPLOAD_BODY_OVERHEAD: usize = 64 * 1024;
+// The header carrying the request id, which is generated if not provided by the client or a proxy.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
 
 // AppState is used to pass the global states to the handlers.
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
-    pub env: Environment<'static>,
+    pub theme: Theme,
+    pub i18n: I18n,
+    // the environment is shared behind a lock so that it can be reloaded in the dev mode.
+    pub env: Arc<RwLock<Environment<'static>>>,
+    // the watcher of the templates directory in the dev mode, it stops watching once dropped.
+    _watcher: Option<Arc<RecommendedWatcher>>,
     pub db: sqlx::MySqlPool,
+    // the storage of the uploaded media files, either a local directory or an S3-compatible bucket.
+    pub media_store: Arc<dyn ObjectStore>,
+    // the dimensions of the uploaded images to render the responsive images.
+    pub images: Images,
+    // the fonts and cache of the generated social cards of the articles.
+    pub og_images: OgImages,
+    // the time when the app started, the cached responses are invalidated after a restart
+    // since the templates and config may have changed.
+    pub started_at: DateTime<Utc>,
 }
 
 impl AppState {
-    pub async fn new() -> Result<Self, Error> {
+    pub async fn new(dev_mode: bool) -> Result<Self, Error> {
         info!("parsing config file");
-        let config = Config::new(CONFIG_FILE_PATH)?;
+        let mut config = Config::new(CONFIG_FILE_PATH)?;
+        if dev_mode {
+            config.enable_dev_mode();
+        }
 
         info!("connecting to the database");
         // connect to the database.
@@ -63,30 +110,150 @@ impl AppState {
         )
         .await?;
 
+        info!("initializing the media storage");
+        let media_store = build_store(&config)?;
+        let images = Images::load(&db).await;
+        let og_images = OgImages::new(&config)?;
+
+        info!("loading the theme");
+        let theme = Theme::load(&config)?;
+        info!("loading the translations");
+        let i18n = I18n::load(&config.lang())?;
+
         info!("building the environment");
-        let env = Self::build_env(&config)?;
+        let env = Arc::new(RwLock::new(Self::build_env(
+            &config, &theme, &i18n, &images,
+        )?));
+        let watcher = if config.dev_mode() {
+            info!("dev mode enabled, watching the templates for changes");
+            Some(Arc::new(Self::watch_templates(
+                env.clone(),
+                &theme.template_dirs(),
+            )?))
+        } else {
+            None
+        };
 
-        Ok(Self { config, env, db })
+        Ok(Self {
+            config,
+            theme,
+            i18n,
+            env,
+            _watcher: watcher,
+            db,
+            media_store,
+            images,
+            og_images,
+            started_at: Utc::now(),
+        })
     }
 
-    fn build_env(config: &Config) -> Result<Environment<'static>, Error> {
+    fn build_env(
+        config: &Config,
+        theme: &Theme,
+        i18n: &I18n,
+        images: &Images,
+    ) -> Result<Environment<'static>, Error> {
         let mut env = Environment::new();
-        // iterate the templates directory and add all the templates.
-        for entry in std::fs::read_dir(TEMPLATES_DIR)? {
-            let path = entry.unwrap().path();
-            if !path.is_file() {
-                continue;
+        // only the existing directories can override the embedded templates.
+        let template_dirs = theme
+            .template_dirs()
+            .into_iter()
+            .filter(|dir| dir.is_dir())
+            .collect::<Vec<_>>();
+        if config.dev_mode() {
+            // load the templates lazily from the disk so they can be reloaded once changed,
+            // the theme's templates are looked up first and the embedded ones are the fallback.
+            let loaders = template_dirs
+                .into_iter()
+                .map(path_loader)
+                .collect::<Vec<_>>();
+            env.set_loader(move |name| {
+                for loader in &loaders {
+                    if let Some(template) = loader(name)? {
+                        return Ok(Some(template));
+                    }
+                }
+                embedded_template(name).map_err(|err| {
+                    minijinja::Error::new(
+                        ErrorKind::InvalidOperation,
+                        "failed loading the template",
+                    )
+                    .with_source(err)
+                })
+            });
+        } else {
+            // add the embedded templates first, so the ones on the disk can override them.
+            for name in embedded_template_names() {
+                if let Some(template_content) = embedded_template(&name)? {
+                    env.add_template_owned(name, template_content)?;
+                }
+            }
+            // iterate the templates directories from the built-in one, so the templates
+            // of the theme can override the built-in ones with the same name.
+            for dir in template_dirs.iter().rev() {
+                for entry in std::fs::read_dir(dir)? {
+                    let path = entry?.path();
+                    if !path.is_file() {
+                        continue;
+                    }
+                    let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    let template_content = std::fs::read_to_string(path)?;
+                    env.add_template_owned(file_name, template_content)?;
+                }
             }
-            let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
-            let template_content = std::fs::read_to_string(path)?;
-            env.add_template_owned(file_name, template_content)?;
         }
         // load the global variables into the environment.
         env.add_global("config", Value::from_object(config.clone()));
+        env.add_global("theme", Value::from_serialize(theme));
+        // load the translation functions into the environment.
+        let i18n_clone = i18n.clone();
+        env.add_function("t", move |key: &str, kwargs: Kwargs| {
+            let args = kwargs
+                .args()
+                .map(|name| Ok((name, kwargs.get::<Value>(name)?.to_string())))
+                .collect::<Result<Vec<_>, minijinja::Error>>()?;
+            let args = args
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .collect::<Vec<_>>();
+            Ok::<_, minijinja::Error>(i18n_clone.t(key, &args))
+        });
+        let i18n_clone = i18n.clone();
+        let timezone = config.timezone();
+        env.add_filter("l10n_date", move |value: &str, style: Option<&str>| {
+            // the datetime is serialized in RFC 3339 format by default.
+            match DateTime::parse_from_rfc3339(value) {
+                Ok(datetime) => i18n_clone
+                    .format_date(&datetime.with_timezone(&timezone), style)
+                    .map_err(|_| {
+                        minijinja::Error::new(
+                            ErrorKind::InvalidOperation,
+                            format!("invalid localized date format of style {:?}", style),
+                        )
+                    }),
+                Err(_) => Ok(value.to_string()),
+            }
+        });
+        // convert the datetime into the configured timezone and format it.
+        env.add_filter("date", move |value: &str, format: Option<&str>| {
+            let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+            match DateTime::parse_from_rfc3339(value) {
+                Ok(datetime) => format_datetime(&datetime.with_timezone(&timezone), format)
+                    .map_err(|_| {
+                        minijinja::Error::new(
+                            ErrorKind::InvalidOperation,
+                            format!("invalid date format {}", format),
+                        )
+                    }),
+                Err(_) => Ok(value.to_string()),
+            }
+        });
         // load the embedded functions into the environment.
         let config_clone = config.clone();
+        let images_clone = images.clone();
         env.add_filter("md_to_html", move |md_content: &str| {
-            Self::md_to_html(&config_clone, md_content)
+            md_to_html(&config_clone, &images_clone, md_content)
         });
         env.add_filter("truncate_str", |value: &str, max_length: usize| {
             if value.chars().count() > max_length {
@@ -96,6 +263,20 @@ impl AppState {
             }
         });
         env.add_filter("to_lowercase", |value: &str| value.to_lowercase());
+        env.add_filter("split_tags", |value: &str| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        });
+        env.add_filter("to_rfc2822", |value: &str| {
+            // the datetime is serialized in RFC 3339 format by default.
+            DateTime::parse_from_rfc3339(value)
+                .map(|datetime| datetime.to_rfc2822())
+                .unwrap_or(value.to_string())
+        });
         env.add_filter("concat_url", |value: &str, uri: &str| {
             if value.ends_with('/') {
                 format!("{}{}", value, uri)
@@ -107,30 +288,93 @@ impl AppState {
         Ok(env)
     }
 
-    fn md_to_html(config: &Config, md_content: &str) -> String {
-        // enable some extension options.
-        let mut options = Options::default();
-        options.extension.strikethrough = true;
-        options.extension.autolink = true;
-        options.render.figure_with_caption = true;
-        // enable the syntax highlight adapter.
-        let mut plugins = Plugins::default();
-        let adapter = syntect::SyntectAdapterBuilder::new()
-            .theme(config.code_syntax_highlight_theme().as_str())
-            .build();
-        plugins.render.codefence_syntax_highlighter = Some(&adapter);
-
-        markdown_to_html_with_plugins(md_content, &options, &plugins)
+    // watch the templates directories and clear the loaded templates once any of them changes,
+    // so that the path loader will load the latest ones on the next rendering.
+    fn watch_templates(
+        env: Arc<RwLock<Environment<'static>>>,
+        template_dirs: &[PathBuf],
+    ) -> Result<RecommendedWatcher, Error> {
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) if !event.kind.is_access() => {
+                    info!("templates changed, reloading: {:?}", event.paths);
+                    env.write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clear_templates();
+                }
+                Ok(_) => {}
+                Err(err) => error!("failed watching the templates: {:?}", err),
+            })?;
+        for dir in template_dirs.iter().filter(|dir| dir.is_dir()) {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+
+        Ok(watcher)
+    }
+
+    // get the validators of the pages that may change along with any article or page.
+    pub async fn site_validators(&self) -> Validators {
+        let articles = Article::get_revision(&self.db).await;
+        let pages = Page::get_revision(&self.db).await;
+        let last_modified = [
+            Some(self.started_at),
+            articles.latest_updated,
+            pages.latest_updated,
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(self.started_at);
+        // the versions are included since an edit may keep `updated_at`.
+        let version = format!(
+            "{}-{}-{}-{}",
+            articles.count, articles.total_version, pages.count, pages.total_version
+        );
+        Validators::new(last_modified, &version)
     }
 
-    pub async fn render_template(&self, template_name: &str, context: Value) -> String {
-        let template = self.env.get_template(template_name).unwrap();
-        template
-            .render(context! {
-                page_titles => Page::get_all_titles(&self.db).await,
-                ..context,
-            })
-            .unwrap()
+    // get the validators of the article page, which only changes along with the article itself
+    // and the pages shown in the navigation.
+    pub async fn article_validators(&self, article: &Article) -> Validators {
+        let pages = Page::get_revision(&self.db).await;
+        let last_modified = [
+            Some(self.started_at),
+            Some(article.updated_at),
+            pages.latest_updated,
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(self.started_at);
+        let version = format!(
+            "{}-{}-{}-{}",
+            article.id.unwrap_or_default(),
+            article.version,
+            pages.count,
+            pages.total_version
+        );
+        Validators::new(last_modified, &version)
+    }
+
+    // render the excerpts of the articles to be shown in the lists, feeds and meta descriptions.
+    pub fn fill_excerpts(&self, articles: &mut [Article]) {
+        for article in articles {
+            article.set_excerpt(excerpt(&self.config, &self.images, &article.content));
+        }
+    }
+
+    pub async fn render_template(
+        &self,
+        template_name: &str,
+        context: Value,
+    ) -> Result<String, Error> {
+        let page_titles = Page::get_all_titles(&self.db).await;
+        let env = self.env.read().unwrap_or_else(PoisonError::into_inner);
+        let template = env.get_template(template_name)?;
+        Ok(template.render(context! {
+            page_titles => page_titles,
+            ..context,
+        })?)
     }
 }
 
@@ -139,9 +383,10 @@ pub struct App {
 }
 
 impl App {
-    pub async fn new() -> Result<Self, Error> {
+    // create the app, the dev mode can be enabled regardless of the config file.
+    pub async fn new(dev_mode: bool) -> Result<Self, Error> {
         Ok(Self {
-            state: AppState::new().await?,
+            state: AppState::new(dev_mode).await?,
         })
     }
 
@@ -155,6 +400,7 @@ impl App {
             .with_signed(Key::generate());
         // authentication layer
         let auth_layer = AuthManagerLayerBuilder::new(self.state.clone(), session_layer).build();
+        let state = Arc::new(self.state.clone());
 
         let admin_router = Router::new()
             .route("/", get(handler_admin))
@@ -170,34 +416,134 @@ impl App {
             .route("/edit/page/:id", get(handler_edit_page_get))
             .route("/edit/page/:id", post(handler_edit_post::<Page>))
             .route("/delete/page/:id", get(handler_delete_post::<Page>))
+            .route("/preview", post(handler_preview_post))
+            .route("/media", get(handler_media_library))
+            .route(
+                "/media",
+                // leave some room for the multipart boundaries and headers besides the file.
+                post(handler_media_upload_post).layer(DefaultBodyLimit::max(
+                    self.state.config.media_max_size() + MEDIA_UPLOAD_BODY_OVERHEAD,
+                )),
+            )
+            .route("/delete/media/:id", get(handler_media_delete))
+            .route(
+                "/autosave/article/new",
+                post(handler_autosave_post::<Article>),
+            )
+            .route(
+                "/autosave/article/:id",
+                post(handler_autosave_post::<Article>),
+            )
+            .route("/autosave/page/new", post(handler_autosave_post::<Page>))
+            .route("/autosave/page/:id", post(handler_autosave_post::<Page>))
+            .route(
+                "/discard_autosave/article/new",
+                post(handler_autosave_discard_post::<Article>),
+            )
+            .route(
+                "/discard_autosave/article/:id",
+                post(handler_autosave_discard_post::<Article>),
+            )
+            .route(
+                "/discard_autosave/page/new",
+                post(handler_autosave_discard_post::<Page>),
+            )
+            .route(
+                "/discard_autosave/page/:id",
+                post(handler_autosave_discard_post::<Page>),
+            )
             .route_layer(login_required!(AppState, login_url = "/login"));
 
-        let app = Router::new()
+        // serve the static files of the theme first and fall back to the built-in ones,
+        // the embedded ones are the last resort if they don't exist on the disk.
+        let config = &self.state.config;
+        let static_dirs = self.state.theme.static_dirs();
+        let static_service = serve_dir(config, &static_dirs[0]).fallback(
+            serve_dir(config, &static_dirs[static_dirs.len() - 1])
+                .fallback(Router::new().fallback(handler_embedded_static)),
+        );
+        let mut app = Router::new()
             .fallback(handler_404)
             // serve the static files
-            .nest_service("/static", ServeDir::new(STATIC_DIR))
+            .nest_service(
+                "/static",
+                SetResponseHeader::if_not_present(
+                    static_service,
+                    CACHE_CONTROL,
+                    self.state.config.static_cache_control(),
+                ),
+            )
+            // serve the uploaded media files from the media storage.
+            .route(&format!("{}/*path", MEDIA_URL_PREFIX), get(handler_media))
             // serve the page handlers
             .route("/", get(handler_home))
             .route("/page/:num", get(handler_page))
             .route("/article/:id", get(handler_article))
+            .route("/article/:id/og.png", get(handler_og_image))
             .route("/articles", get(handler_articles))
             .route("/tag/:tag", get(handler_tag))
+            .route("/tag/:tag/feed", get(handler_tag_feed))
             .route("/tags", get(handler_tags))
             .route("/feed", get(handler_feed))
+            .route("/feed.json", get(handler_json_feed))
+            .route("/rss.xml", get(handler_rss))
+            .route("/sitemap.xml", get(handler_sitemap))
+            .route("/robots.txt", get(handler_robots))
             .route("/ping", get(handler_ping))
             .route("/:page", get(handler_custom_page))
             .route("/login", get(handler_login_get))
             .route("/login", post(handler_login_post))
             .route("/logout", get(handler_logout))
             // nest the admin router under the `/admin` path.
-            .nest("/admin", admin_router)
+            .nest("/admin", admin_router);
+        // serve the Micropub endpoint only if it's enabled.
+        if self.state.config.micropub_enabled() {
+            app = app.route(
+                "/micropub",
+                get(handler_micropub_get).post(handler_micropub_post),
+            );
+        }
+        // serve the MetaWeblog API for the desktop blog editors only if it's enabled.
+        if self.state.config.xmlrpc_enabled() {
+            app = app.route(
+                "/xmlrpc",
+                post(handler_xmlrpc).layer(DefaultBodyLimit::max(XMLRPC_BODY_LIMIT)),
+            );
+        }
+        // render the error page for the responses converted from the errors, including the ones
+        // from the extractors and the fallback.
+        app = app.layer(middleware::from_fn_with_state(state.clone(), handler_error));
+        if let Some(compression_layer) = compression_layer(&self.state.config) {
+            app = app.layer(compression_layer);
+        }
+        let app = app
             .layer(auth_layer)
             .layer(
                 TraceLayer::new_for_http()
-                    .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                    .make_span_with(|request: &Request| {
+                        // record the request id so the logs of a failed request can be found.
+                        let request_id = request
+                            .headers()
+                            .get(REQUEST_ID_HEADER)
+                            .and_then(|value| value.to_str().ok())
+                            .unwrap_or_default();
+                        info_span!(
+                            "request",
+                            method = %request.method(),
+                            uri = %request.uri(),
+                            request_id = request_id,
+                        )
+                    })
                     .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
             )
-            .with_state(Arc::new(self.state.clone()));
+            .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+                REQUEST_ID_HEADER,
+            )))
+            .layer(SetRequestIdLayer::new(
+                HeaderName::from_static(REQUEST_ID_HEADER),
+                MakeRequestUuid,
+            ))
+            .with_state(state);
 
         let listener = tokio::net::TcpListener::bind(self.state.config.server_url()).await?;
         info!("listening on {}", listener.local_addr()?);
@@ -206,3 +552,120 @@ impl App {
         Ok(())
     }
 }
+
+// Serve the precompressed variants if they exist, except in the dev mode since they may be stale
+// while editing the static files.
+fn serve_dir(config: &Config, dir: &PathBuf) -> ServeDir {
+    if config.dev_mode() {
+        ServeDir::new(dir)
+    } else {
+        ServeDir::new(dir)
+            .precompressed_br()
+            .precompressed_gzip()
+            .precompressed_zstd()
+    }
+}
+
+// Compress the responses with the enabled algorithms, `None` if the compression is disabled.
+fn compression_layer(config: &Config) -> Option<CompressionLayer> {
+    if !config.compression_enabled() {
+        return None;
+    }
+    let (gzip, br, zstd) = config.compression_algorithms();
+    Some(
+        CompressionLayer::new()
+            .gzip(gzip)
+            .br(br)
+            .zstd(zstd)
+            .no_deflate(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+            Request,
+        },
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::tests::test_config;
+
+    impl AppState {
+        // build the state with a lazily connected pool, so that the handlers not querying
+        // the database can be tested without one.
+        pub(crate) fn for_test(config: Config) -> Self {
+            let theme = Theme::load(&config).unwrap();
+            let i18n = I18n::load("en").unwrap();
+            let images = Images::default();
+            let env = AppState::build_env(&config, &theme, &i18n, &images).unwrap();
+            let db = sqlx::mysql::MySqlPoolOptions::new()
+                .acquire_timeout(std::time::Duration::from_millis(100))
+                .connect_lazy("mysql://rsomhap@127.0.0.1:1/rsomhap")
+                .unwrap();
+            Self {
+                og_images: OgImages::new(&config).unwrap(),
+                config,
+                theme,
+                i18n,
+                env: Arc::new(RwLock::new(env)),
+                _watcher: None,
+                db,
+                media_store: Arc::new(object_store::memory::InMemory::new()),
+                images,
+                started_at: Utc::now(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compression() {
+        let config = test_config("", "");
+        let request = |encoding: &str| {
+            Request::get("/style.css")
+                .header(ACCEPT_ENCODING, encoding)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // the precompressed variant is served as it is.
+        let dir = std::env::temp_dir().join(format!("rsomhap-static-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("style.css"), "body {}").unwrap();
+        std::fs::write(dir.join("style.css.br"), "brotli").unwrap();
+        let response = serve_dir(&config, &dir)
+            .oneshot(request("br"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "br");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "brotli");
+        let response = serve_dir(&config, &dir)
+            .oneshot(request("gzip"))
+            .await
+            .unwrap();
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the responses are compressed with the enabled algorithms only.
+        let app = |config: &Config| {
+            let app = Router::new().route("/style.css", get(|| async { "body {}".repeat(100) }));
+            match compression_layer(config) {
+                Some(compression_layer) => app.layer(compression_layer),
+                None => app,
+            }
+        };
+        let response = app(&config).oneshot(request("gzip")).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        let config = test_config("", "[compression]\ngzip = false");
+        let response = app(&config).oneshot(request("gzip")).await.unwrap();
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        let config = test_config("", "[compression]\nenable = false");
+        assert!(compression_layer(&config).is_none());
+    }
+}
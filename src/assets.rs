@@ -0,0 +1,97 @@
+use axum::{
+    extract::Request,
+    http::{
+        header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use rust_embed::{EmbeddedFile, RustEmbed};
+
+use crate::Error;
+
+// The default config embedded into the binary, used if the config file doesn't exist on the disk.
+pub const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+
+// The built-in templates embedded into the binary, the ones with the same name
+// on the disk or in the theme take precedence over them.
+#[derive(RustEmbed)]
+#[folder = "templates/"]
+struct EmbeddedTemplates;
+
+// The built-in static files embedded into the binary, the ones with the same path
+// on the disk or in the theme take precedence over them.
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct EmbeddedStatic;
+
+// The built-in translation catalogs embedded into the binary, the ones with the same name
+// on the disk take precedence over them.
+#[derive(RustEmbed)]
+#[folder = "locales/"]
+struct EmbeddedLocales;
+
+// Get the names of all the embedded templates.
+pub fn embedded_template_names() -> impl Iterator<Item = String> {
+    EmbeddedTemplates::iter().map(|name| name.into_owned())
+}
+
+// Get the content of an embedded template, `None` if it doesn't exist.
+pub fn embedded_template(name: &str) -> Result<Option<String>, Error> {
+    EmbeddedTemplates::get(name).map(into_string).transpose()
+}
+
+// Get the content of an embedded translation catalog, `None` if the language isn't built-in.
+pub fn embedded_locale(lang: &str) -> Result<Option<String>, Error> {
+    EmbeddedLocales::get(&format!("{}.toml", lang))
+        .map(into_string)
+        .transpose()
+}
+
+// Serve the embedded static files, used as the fallback of the static files on the disk.
+pub async fn handler_embedded_static(request: Request) -> Response {
+    let path = request.uri().path().trim_start_matches('/');
+    let file = match EmbeddedStatic::get(path) {
+        Some(file) => file,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    // the files never change until the binary is rebuilt, so use their hashes as the ETags.
+    let etag = format!("\"{}\"", hex_encode(&file.metadata.sha256_hash()));
+    if request
+        .headers()
+        .get(IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes() == etag.as_bytes())
+    {
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+    let mut response = file.data.into_owned().into_response();
+    let headers = response.headers_mut();
+    if let Ok(content_type) = HeaderValue::from_str(file.metadata.mimetype()) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(ETAG, etag);
+    }
+    response
+}
+
+fn into_string(file: EmbeddedFile) -> Result<String, Error> {
+    String::from_utf8(file.data.into_owned())
+        .map_err(|err| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_templates() {
+        assert!(embedded_template_names().any(|name| name == "layout.html"));
+        assert!(embedded_template("layout.html").unwrap().is_some());
+        assert!(embedded_template("not_exist.html").unwrap().is_none());
+    }
+}
@@ -0,0 +1,109 @@
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+// The HTTP-date format defined in RFC 9110, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+// Validators used to answer the conditional requests without rendering the response.
+#[derive(Debug, PartialEq)]
+pub struct Validators {
+    etag: String,
+    last_modified: DateTime<Utc>,
+}
+
+impl Validators {
+    // Build the validators from the last modified time and a version that changes along with the content,
+    // e.g. the number of the articles so that deleting one can also be noticed.
+    pub fn new(last_modified: DateTime<Utc>, version: &str) -> Self {
+        Self {
+            etag: format!("W/\"{:x}-{}\"", last_modified.timestamp(), version),
+            last_modified,
+        }
+    }
+
+    // Check if the client's cached copy is still fresh, `If-None-Match` takes precedence over
+    // `If-Modified-Since` as described in RFC 9110.
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            let if_none_match = if_none_match.to_str().unwrap_or_default();
+            // use the weak comparison since the ETag is weak.
+            let etag = self.etag.trim_start_matches("W/");
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+        }
+        headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .is_some_and(|since| self.last_modified.timestamp() <= since.timestamp())
+    }
+
+    // Return a `304 Not Modified` response if the client's cached copy is still fresh.
+    pub fn check(&self, headers: &HeaderMap, cache_control: HeaderValue) -> Option<Response> {
+        if self.is_fresh(headers) {
+            Some(self.attach(StatusCode::NOT_MODIFIED, cache_control))
+        } else {
+            None
+        }
+    }
+
+    // Attach the validators and the `Cache-Control` header to the response.
+    pub fn attach(&self, response: impl IntoResponse, cache_control: HeaderValue) -> Response {
+        let mut response = response.into_response();
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(last_modified) =
+            HeaderValue::from_str(&self.last_modified.format(HTTP_DATE_FORMAT).to_string())
+        {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        headers.insert(CACHE_CONTROL, cache_control);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validators() {
+        let last_modified = "2024-08-22T01:06:04Z".parse::<DateTime<Utc>>().unwrap();
+        let validators = Validators::new(last_modified, "3-1");
+        let cache_control = HeaderValue::from_static("no-cache");
+
+        let mut headers = HeaderMap::new();
+        assert!(validators.check(&headers, cache_control.clone()).is_none());
+
+        let response = validators.attach("hello", cache_control.clone());
+        let etag = response.headers().get(ETAG).unwrap().clone();
+        let last_modified = response.headers().get(LAST_MODIFIED).unwrap().clone();
+        assert_eq!(last_modified, "Thu, 22 Aug 2024 01:06:04 GMT");
+
+        headers.insert(IF_NONE_MATCH, etag);
+        let response = validators.check(&headers, cache_control.clone()).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        // `If-None-Match` takes precedence over `If-Modified-Since`.
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        headers.insert(IF_MODIFIED_SINCE, last_modified);
+        assert!(validators.check(&headers, cache_control.clone()).is_none());
+
+        headers.remove(IF_NONE_MATCH);
+        assert!(validators.check(&headers, cache_control.clone()).is_some());
+        headers.insert(
+            IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Thu, 22 Aug 2024 01:06:03 GMT"),
+        );
+        assert!(validators.check(&headers, cache_control).is_none());
+    }
+}
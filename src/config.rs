@@ -1,12 +1,15 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
+use axum::http::HeaderValue;
+use chrono_tz::Tz;
 use minijinja::{
     value::{Enumerator, Object},
     Value,
 };
 use serde::Deserialize;
+use tracing::info;
 
-use crate::error::Error;
+use crate::{assets::DEFAULT_CONFIG, error::Error, i18n::DEFAULT_LANG};
 
 #[derive(Clone, Debug, Deserialize)]
 struct Deploy {
@@ -20,6 +23,10 @@ struct Meta {
     blog_url: String,
     blog_author: String,
     about_url: Option<String>,
+    lang: Option<String>,
+    timezone: Option<String>,
+    // the image of the social cards for the articles without any image.
+    default_image: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -32,6 +39,8 @@ struct Admin {
 struct Style {
     article_per_page: u32,
     code_syntax_highlight_theme: String,
+    // the max chars of the auto excerpts of the articles without the `<!--more-->` separator.
+    excerpt_length: Option<usize>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -137,6 +146,235 @@ impl Object for TwitterCard {
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Feed {
+    limit: Option<u32>,
+    full_content: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Cache {
+    pages: Option<String>,
+    feed: Option<String>,
+    #[serde(rename = "static")]
+    static_files: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct Compression {
+    enable: bool,
+    gzip: bool,
+    br: bool,
+    zstd: bool,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            gzip: true,
+            br: true,
+            zstd: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct Robots {
+    disallow: Vec<String>,
+}
+
+impl Default for Robots {
+    fn default() -> Self {
+        Self {
+            disallow: vec!["/admin".to_string(), "/login".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Micropub {
+    enable: bool,
+    token: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct XmlRpc {
+    enable: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct Media {
+    dir: Option<String>,
+    storage: MediaStorage,
+    max_size_mb: u64,
+    // the MIME types sniffed from the content that are allowed to be uploaded.
+    allowed_types: Vec<String>,
+    // the widths of the resized variants of the uploaded images.
+    image_widths: Vec<u32>,
+    // the `sizes` attribute of the responsive images.
+    image_sizes: String,
+    // offer the WebP variants of the uploaded images.
+    webp: bool,
+    // the local directory caching the resized variants.
+    cache_dir: Option<String>,
+    s3: Option<MediaS3>,
+}
+
+impl Default for Media {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            storage: MediaStorage::default(),
+            max_size_mb: 10,
+            allowed_types: [
+                "image/png",
+                "image/jpeg",
+                "image/gif",
+                "image/webp",
+                "image/avif",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            image_widths: vec![480, 960, 1440],
+            image_sizes: "(max-width: 800px) 100vw, 800px".to_string(),
+            webp: true,
+            cache_dir: None,
+            s3: None,
+        }
+    }
+}
+
+// The comrak extensions enabled in the Markdown rendering.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MarkdownExtensions {
+    pub strikethrough: bool,
+    pub autolink: bool,
+    pub tables: bool,
+    pub footnotes: bool,
+    pub tasklist: bool,
+    pub description_lists: bool,
+    pub superscript: bool,
+    // how the `$...$` and `$$...$$` math is rendered.
+    pub math: MathRenderer,
+}
+
+impl Default for MarkdownExtensions {
+    fn default() -> Self {
+        Self {
+            strikethrough: true,
+            autolink: true,
+            tables: false,
+            footnotes: false,
+            tasklist: false,
+            description_lists: false,
+            superscript: false,
+            math: MathRenderer::default(),
+        }
+    }
+}
+
+// How the math is rendered, `none` leaves the dollar signs as they are.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MathRenderer {
+    #[default]
+    None,
+    // the MathML rendered on the server, which is supported by the modern browsers natively.
+    MathML,
+    // the `\(...\)` and `\[...\]` markup rendered by the KaTeX auto-render extension.
+    KaTeX,
+}
+
+impl MathRenderer {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MathRenderer::None => "none",
+            MathRenderer::MathML => "mathml",
+            MathRenderer::KaTeX => "katex",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct Toc {
+    enable: bool,
+    // the min number of headings for an article to get the automatic table of contents.
+    min_headings: usize,
+    // replace the `[toc]` paragraph in the content with the table of contents.
+    marker: bool,
+}
+
+impl Default for Toc {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            min_headings: 3,
+            marker: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct OgImage {
+    enable: bool,
+    // the font file overriding the embedded ones, e.g. one covering CJK.
+    font: Option<String>,
+    // the local directory caching the generated cards.
+    cache_dir: Option<String>,
+}
+
+impl Default for OgImage {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            font: None,
+            cache_dir: None,
+        }
+    }
+}
+
+// Where the uploaded media files are stored.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaStorage {
+    #[default]
+    Local,
+    S3,
+}
+
+// The S3-compatible bucket to store the media files, the missing credentials are read
+// from the `AWS_*` environment variables.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct MediaS3 {
+    pub endpoint: Option<String>,
+    pub bucket: String,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Theme {
+    name: Option<String>,
+    dir: Option<String>,
+    // the options overriding the defaults in the theme manifest.
+    #[serde(default)]
+    options: toml::Table,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Dev {
+    enable: bool,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     deploy: Deploy,
@@ -147,18 +385,44 @@ pub struct Config {
     giscus: Giscus,
     analytics: Analytics,
     twitter_card: TwitterCard,
+    #[serde(default)]
+    feed: Feed,
+    #[serde(default)]
+    cache: Cache,
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    robots: Robots,
+    #[serde(default)]
+    micropub: Micropub,
+    #[serde(default)]
+    xmlrpc: XmlRpc,
+    #[serde(default)]
+    media: Media,
+    #[serde(default)]
+    markdown: MarkdownExtensions,
+    #[serde(default)]
+    toc: Toc,
+    #[serde(default)]
+    og_image: OgImage,
+    #[serde(default)]
+    theme: Theme,
+    #[serde(default)]
+    dev: Dev,
 }
 
 impl Config {
     pub fn new(path: &str) -> Result<Self, Error> {
-        let config_content = std::fs::read_to_string(path)?;
-        let mut config: Self = toml::from_str(&config_content).map_err(Error::Toml)?;
-        // get some environment variables.
-        config.load_env_vars()?;
-        // validate the config to ensure the deployment is correct.
-        config.validate()?;
-
-        Ok(config)
+        let config_content = match std::fs::read_to_string(path) {
+            Ok(config_content) => config_content,
+            // fall back to the embedded default config so that a single binary is deployable.
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("config file {} not found, using the embedded one", path);
+                DEFAULT_CONFIG.to_string()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        config_content.parse()
     }
 
     fn load_env_vars(&mut self) -> Result<(), Error> {
@@ -168,6 +432,9 @@ impl Config {
         if let Ok(plausible_domain) = std::env::var("PLAUSIBLE_DOMAIN") {
             self.analytics.plausible = Some(plausible_domain);
         }
+        if let Ok(micropub_token) = std::env::var("MICROPUB_TOKEN") {
+            self.micropub.token = Some(micropub_token);
+        }
         Ok(())
     }
 
@@ -190,6 +457,67 @@ impl Config {
                 "invalid MySQL config, please specify the connection URL or the username, password, host, port and database".to_string(),
             ));
         }
+        // check the cache config.
+        for cache_control in [
+            &self.cache.pages,
+            &self.cache.feed,
+            &self.cache.static_files,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if HeaderValue::from_str(cache_control).is_err() {
+                return Err(Error::ConfigValidation(format!(
+                    "invalid cache config, {} is not a valid Cache-Control header value",
+                    cache_control
+                )));
+            }
+        }
+        // check the timezone config.
+        if let Some(timezone) = &self.meta.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                return Err(Error::ConfigValidation(format!(
+                    "invalid meta config, {} is not a valid IANA timezone",
+                    timezone
+                )));
+            }
+        }
+        // check the theme config.
+        if let Some(name) = &self.theme.name {
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return Err(Error::ConfigValidation(format!(
+                    "invalid theme config, {} is not a valid theme name",
+                    name
+                )));
+            }
+        }
+        // check the media config.
+        if self.media.storage == MediaStorage::S3
+            && self.media.s3.as_ref().is_none_or(|s3| s3.bucket.is_empty())
+        {
+            return Err(Error::ConfigValidation(
+                "invalid media config, please specify the bucket in `[media.s3]` to use the S3 storage"
+                    .to_string(),
+            ));
+        }
+        if self.media.image_widths.contains(&0) {
+            return Err(Error::ConfigValidation(
+                "invalid media config, the image widths must be positive".to_string(),
+            ));
+        }
+        // check the Micropub config.
+        if self.micropub.enable
+            && self
+                .micropub
+                .token
+                .as_ref()
+                .is_none_or(|token| token.is_empty())
+        {
+            return Err(Error::ConfigValidation(
+                "invalid Micropub config, please specify the token to enable the endpoint"
+                    .to_string(),
+            ));
+        }
 
         Ok(())
     }
@@ -229,6 +557,35 @@ impl Config {
         }
     }
 
+    pub fn blog_name(&self) -> String {
+        self.meta.blog_name.clone()
+    }
+
+    pub fn blog_url(&self) -> String {
+        self.meta.blog_url.clone()
+    }
+
+    pub fn default_image(&self) -> Option<String> {
+        self.meta
+            .default_image
+            .clone()
+            .filter(|default_image| !default_image.is_empty())
+    }
+
+    // get the timezone to display the dates in, which is validated already.
+    pub fn timezone(&self) -> Tz {
+        self.meta
+            .timezone
+            .as_deref()
+            .and_then(|timezone| timezone.parse().ok())
+            .unwrap_or(Tz::UTC)
+    }
+
+    // get the language of the UI, which is also used to localize the dates.
+    pub fn lang(&self) -> String {
+        self.meta.lang.clone().unwrap_or(DEFAULT_LANG.to_string())
+    }
+
     pub fn admin_username(&self) -> String {
         self.admin.username.clone()
     }
@@ -241,9 +598,180 @@ impl Config {
         self.style.article_per_page
     }
 
+    pub fn excerpt_length(&self) -> usize {
+        self.style.excerpt_length.unwrap_or(200)
+    }
+
     pub fn code_syntax_highlight_theme(&self) -> String {
         self.style.code_syntax_highlight_theme.clone()
     }
+
+    // get the max number of articles in the feeds, 0 means no limit.
+    pub fn feed_limit(&self) -> u32 {
+        self.feed.limit.unwrap_or(20)
+    }
+
+    pub fn feed_full_content(&self) -> bool {
+        self.feed.full_content.unwrap_or(true)
+    }
+
+    pub fn pages_cache_control(&self) -> HeaderValue {
+        self.cache_control(&self.cache.pages, "no-cache")
+    }
+
+    pub fn feed_cache_control(&self) -> HeaderValue {
+        self.cache_control(&self.cache.feed, "public, max-age=600")
+    }
+
+    pub fn static_cache_control(&self) -> HeaderValue {
+        self.cache_control(&self.cache.static_files, "public, max-age=3600")
+    }
+
+    fn cache_control(&self, value: &Option<String>, default: &'static str) -> HeaderValue {
+        // never let the browsers cache anything in the dev mode to see the changes immediately.
+        if self.dev_mode() {
+            return HeaderValue::from_static("no-store");
+        }
+        value
+            .as_deref()
+            .and_then(|value| HeaderValue::from_str(value).ok())
+            .unwrap_or(HeaderValue::from_static(default))
+    }
+
+    pub fn compression_enabled(&self) -> bool {
+        self.compression.enable
+    }
+
+    // get whether the gzip, brotli and zstd compression algorithms are enabled respectively.
+    pub fn compression_algorithms(&self) -> (bool, bool, bool) {
+        (
+            self.compression.gzip,
+            self.compression.br,
+            self.compression.zstd,
+        )
+    }
+
+    pub fn media_dir(&self) -> String {
+        self.media.dir.clone().unwrap_or("media".to_string())
+    }
+
+    pub fn media_storage(&self) -> MediaStorage {
+        self.media.storage
+    }
+
+    pub fn media_s3(&self) -> Option<&MediaS3> {
+        self.media.s3.as_ref()
+    }
+
+    // get the max size of an uploaded media file in bytes.
+    pub fn media_max_size(&self) -> usize {
+        (self.media.max_size_mb * 1024 * 1024) as usize
+    }
+
+    pub fn media_allowed_types(&self) -> &[String] {
+        &self.media.allowed_types
+    }
+
+    pub fn media_image_widths(&self) -> &[u32] {
+        &self.media.image_widths
+    }
+
+    pub fn media_image_sizes(&self) -> &str {
+        &self.media.image_sizes
+    }
+
+    pub fn media_webp_enabled(&self) -> bool {
+        self.media.webp
+    }
+
+    pub fn media_cache_dir(&self) -> String {
+        self.media
+            .cache_dir
+            .clone()
+            .unwrap_or("media_cache".to_string())
+    }
+
+    pub fn markdown_extensions(&self) -> &MarkdownExtensions {
+        &self.markdown
+    }
+
+    pub fn toc_enabled(&self) -> bool {
+        self.toc.enable
+    }
+
+    pub fn toc_min_headings(&self) -> usize {
+        self.toc.min_headings
+    }
+
+    pub fn toc_marker_enabled(&self) -> bool {
+        self.toc.marker
+    }
+
+    pub fn og_image_enabled(&self) -> bool {
+        self.og_image.enable
+    }
+
+    pub fn og_image_font(&self) -> Option<&str> {
+        self.og_image
+            .font
+            .as_deref()
+            .filter(|font| !font.is_empty())
+    }
+
+    pub fn og_image_cache_dir(&self) -> String {
+        self.og_image
+            .cache_dir
+            .clone()
+            .unwrap_or("og_cache".to_string())
+    }
+
+    pub fn micropub_enabled(&self) -> bool {
+        self.micropub.enable
+    }
+
+    pub fn micropub_token(&self) -> Option<&str> {
+        self.micropub.token.as_deref()
+    }
+
+    pub fn xmlrpc_enabled(&self) -> bool {
+        self.xmlrpc.enable
+    }
+
+    // get the name of the selected theme, `None` means the built-in one.
+    pub fn theme_name(&self) -> Option<&str> {
+        self.theme.name.as_deref()
+    }
+
+    pub fn themes_dir(&self) -> String {
+        self.theme.dir.clone().unwrap_or("themes".to_string())
+    }
+
+    pub fn theme_options(&self) -> &toml::Table {
+        &self.theme.options
+    }
+
+    pub fn dev_mode(&self) -> bool {
+        self.dev.enable
+    }
+
+    // enable the dev mode regardless of the config file, e.g. by the `--dev` command line flag.
+    pub fn enable_dev_mode(&mut self) {
+        self.dev.enable = true;
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(config_content: &str) -> Result<Self, Error> {
+        let mut config: Self = toml::from_str(config_content).map_err(Error::Toml)?;
+        // get some environment variables.
+        config.load_env_vars()?;
+        // validate the config to ensure the deployment is correct.
+        config.validate()?;
+
+        Ok(config)
+    }
 }
 
 impl Object for Config {
@@ -254,10 +782,16 @@ impl Object for Config {
             "blog_url" => Some(Value::from(self.meta.blog_url.clone())),
             "blog_author" => Some(Value::from(self.meta.blog_author.clone())),
             "about_url" => Some(Value::from(self.meta.about_url.clone())),
+            "lang" => Some(Value::from(self.lang())),
+            "timezone" => Some(Value::from(self.timezone().name())),
             "article_per_page" => Some(Value::from(self.style.article_per_page)),
             "giscus" => Some(Value::from_object(self.giscus.clone())),
             "analytics" => Some(Value::from_object(self.analytics.clone())),
             "twitter_card" => Some(Value::from_object(self.twitter_card.clone())),
+            "feed_full_content" => Some(Value::from(self.feed_full_content())),
+            "robots_disallow" => Some(Value::from(self.robots.disallow.clone())),
+            "micropub_enabled" => Some(Value::from(self.micropub.enable)),
+            "markdown_math" => Some(Value::from(self.markdown.math.as_str())),
             _ => None,
         }
     }
@@ -268,10 +802,86 @@ impl Object for Config {
             "blog_url",
             "blog_author",
             "about_url",
+            "lang",
+            "timezone",
             "article_per_page",
             "giscus",
             "analytics",
             "twitter_card",
+            "feed_full_content",
+            "robots_disallow",
+            "micropub_enabled",
+            "markdown_math",
         ])
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // build a config from the required sections only, `meta` is appended to `[meta]` and
+    // `sections` holds the optional sections, so each test spells out what it depends on.
+    pub(crate) fn test_config(meta: &str, sections: &str) -> Config {
+        format!(
+            r#"
+[deploy]
+host = "127.0.0.1"
+port = 5299
+
+[meta]
+blog_name = "rsomhaP"
+blog_url = "https://example.com"
+blog_author = "JmPotato"
+{meta}
+
+[admin]
+username = "rsomhaP"
+
+[style]
+article_per_page = 15
+code_syntax_highlight_theme = "base16-ocean.light"
+
+[mysql]
+connection_url = "mysql://rsomhap@127.0.0.1:1/rsomhap"
+
+[giscus]
+enable = false
+category = ""
+category_id = ""
+emit_metadata = "0"
+input_position = "top"
+lang = "en"
+loading = ""
+mapping = "og:title"
+reactions_enabled = "1"
+repo = ""
+repo_id = ""
+theme = "light"
+
+[analytics]
+
+[twitter_card]
+enabled = false
+user_id = ""
+
+{sections}
+"#
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_config_from_str() {
+        let config = test_config("", "");
+        assert_eq!(config.blog_url(), "https://example.com");
+        assert_eq!(config.feed_limit(), 20);
+        assert!(config.feed_full_content());
+        let config = test_config("", "[feed]\nlimit = 5\nfull_content = false");
+        assert_eq!(config.feed_limit(), 5);
+        assert!(!config.feed_full_content());
+        // the missing required sections.
+        assert!(matches!("[deploy]".parse::<Config>(), Err(Error::Toml(_))));
+    }
+}
@@ -1,3 +1,9 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tracing::error;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -12,6 +18,15 @@ pub enum Error {
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
 
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    #[error(transparent)]
+    ObjectStore(#[from] object_store::Error),
+
     #[error(transparent)]
     TaskJoin(#[from] tokio::task::JoinError),
 
@@ -23,4 +38,93 @@ pub enum Error {
 
     #[error("page with same title {0} already exists")]
     PageTitleExists(String),
+
+    #[error("entity {0} has been modified since the editor loaded it")]
+    EditConflict(i32),
+
+    #[error("authentication failed: {0}")]
+    Authentication(String),
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("bad request: {0}")]
+    BadRequest(String),
+
+    #[error("media file exceeds the max size of {0} bytes")]
+    MediaTooLarge(usize),
+
+    #[error("media type {0} is not allowed")]
+    MediaTypeNotAllowed(String),
+}
+
+impl Error {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::EditConflict(_) => StatusCode::CONFLICT,
+            Error::MediaTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::MediaTypeNotAllowed(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// The error page attached to the response converted from `Error`, which will be rendered
+// by the error handler middleware since the app state is not available here.
+#[derive(Clone, Debug)]
+pub struct ErrorPage {
+    pub title: String,
+    // the key of the translated message.
+    pub message_key: String,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let message_key = match self {
+            Error::NotFound => "error.not_found",
+            Error::BadRequest(_) | Error::MediaTooLarge(_) | Error::MediaTypeNotAllowed(_) => {
+                "error.bad_request"
+            }
+            Error::EditConflict(_) => "error.conflict",
+            _ => "error.internal",
+        };
+        if status.is_server_error() {
+            error!("failed handling the request: {:?}", self);
+        }
+
+        let mut response = status.into_response();
+        response.extensions_mut().insert(ErrorPage {
+            title: status.as_u16().to_string(),
+            message_key: message_key.to_string(),
+        });
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_into_response() {
+        let response = Error::NotFound.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.extensions().get::<ErrorPage>().unwrap().title,
+            "404"
+        );
+
+        let response = Error::BadRequest("invalid form".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = Error::InvalidMySQLConfig.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.extensions().get::<ErrorPage>().unwrap().title,
+            "500"
+        );
+    }
 }
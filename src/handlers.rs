@@ -1,24 +1,30 @@
 use std::{collections::HashMap, sync::Arc};
 
 use axum::{
-    body::Body,
-    extract::{Query, State},
-    http::{header::CONTENT_TYPE, Response, StatusCode},
-    response::{Html, IntoResponse, Redirect},
+    extract::{multipart::MultipartError, Multipart, Query, Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
 use axum_login::AuthSession;
 use chrono::Datelike;
 use minijinja::context;
-use rand::{thread_rng, Rng};
-use regex::Regex;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde::Deserialize;
 use tracing::{error, info};
 
 use crate::{
-    app::AppState,
+    app::{AppState, REQUEST_ID_HEADER},
     auth::Credentials,
-    models::{Article, Page, Tags, User},
+    config::Config,
+    error::ErrorPage,
+    markdown::{md_to_html, render_article},
+    media::{delete_media, save_media},
+    models::{Article, Autosave, MediaFile, Page, Tags, User},
     render_template_with_context,
     utils::{Editable, EditorPath, Entity, Path},
     Error,
@@ -26,36 +32,49 @@ use crate::{
 
 const ADMIN_URL: &str = "/admin";
 const CHANGE_PW_URL: &str = "/admin/change_password";
+const MEDIA_LIBRARY_URL: &str = "/admin/media";
 
-pub async fn handler_home(state: State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
-    handler_page(state, Path(1)).await
+pub async fn handler_home(
+    state: State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    handler_page(state, Path(1), headers).await
 }
 
 pub async fn handler_page(
     State(state): State<Arc<AppState>>,
     Path(page_num): Path<i32>,
-) -> Result<Html<String>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, Error> {
     // validate `page_num` before querying the database.
     if page_num <= 0 {
-        return handler_404(State(state)).await;
+        return Err(Error::NotFound);
     }
     let total_article_count = Article::get_total_count(&state.db).await as u32;
     let article_per_page = state.config.article_per_page();
     let max_page = (total_article_count as f32 / article_per_page as f32).ceil() as u32;
     if max_page != 0 && page_num as u32 > max_page {
-        return handler_404(State(state)).await;
+        return Err(Error::NotFound);
     }
-    let articles = Article::get_on_page(&state.db, page_num as u32, article_per_page).await;
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
+    let mut articles = Article::get_on_page(&state.db, page_num as u32, article_per_page).await;
+    state.fill_excerpts(&mut articles);
 
-    Ok(render_template_with_context!(
-        state,
-        "home.html",
-        context! {
-            articles => articles,
-            total_article_count => total_article_count,
-            page_num => page_num,
-            max_page => max_page,
-        },
+    Ok(validators.attach(
+        render_template_with_context!(
+            state,
+            "home.html",
+            context! {
+                articles => articles,
+                total_article_count => total_article_count,
+                page_num => page_num,
+                max_page => max_page,
+            },
+        ),
+        state.config.pages_cache_control(),
     ))
 }
 
@@ -63,9 +82,21 @@ pub async fn handler_article(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     auth_session: AuthSession<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    if let Some(article) = Article::get_by_id(&state.db, id).await {
-        return Ok(render_template_with_context!(
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    if let Some(mut article) = Article::get_by_id(&state.db, id).await {
+        let logged_in = auth_session.user.is_some();
+        // skip the conditional requests for the admin since the page has admin-only operations.
+        let validators = state.article_validators(&article).await;
+        if !logged_in {
+            if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+                return Ok(response);
+            }
+        }
+        // render the content only once for the HTML, the table of contents and the description.
+        let rendered = render_article(&state.config, &state.images, &article.content);
+        article.set_excerpt(rendered.excerpt);
+        let response = render_template_with_context!(
             state,
             "article.html",
             context! {
@@ -75,36 +106,63 @@ pub async fn handler_article(
                     .split(',')
                     .map(|s| s.trim().to_string())
                     .collect::<Vec<String>>(),
-                image => {
-                    // find all image URLs in the article markdown content and choose one randomly.
-                    let re = Regex::new(r"\!\[.*?\]\((.*?)\)").unwrap();
-                    let mut image_urls: Vec<String> = vec![];
-                    for (_, [image_url]) in re.captures_iter(&article.content).map(|c| c.extract()) {
-                        image_urls.push(image_url.to_string());
-                    }
-                    if image_urls.is_empty() {
-                        None
-                    } else {
-                        Some(image_urls[thread_rng().gen_range(0..image_urls.len())].clone())
-                    }
-                },
-                logged_in => auth_session.user.is_some(),
+                image => social_image(&state.config, &article, id),
+                content_html => rendered.html,
+                toc => if article.hide_toc { Vec::new() } else { rendered.toc },
+                logged_in => logged_in,
             },
-        ));
+        );
+        return Ok(if logged_in {
+            ([(CACHE_CONTROL, "no-store")], response).into_response()
+        } else {
+            validators.attach(response, state.config.pages_cache_control())
+        });
+    }
+    Err(Error::NotFound)
+}
+
+// Choose the social card image deterministically so that the card stays the same.
+fn social_image(config: &Config, article: &Article, id: i32) -> Option<String> {
+    article
+        .social_image()
+        .or_else(|| {
+            config
+                .og_image_enabled()
+                .then(|| format!("/article/{}/og.png", id))
+        })
+        .or_else(|| config.default_image())
+        .map(|image| absolute_url(&config.blog_url(), &image))
+}
+
+// Resolve the URL against the blog URL, the crawlers of the social cards require absolute URLs.
+fn absolute_url(blog_url: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!(
+            "{}/{}",
+            blog_url.trim_end_matches('/'),
+            url.trim_start_matches('/')
+        )
     }
-    handler_404(State(state)).await
 }
 
 pub async fn handler_tag(
     State(state): State<Arc<AppState>>,
     Path(tag): Path<String>,
-) -> Result<Html<String>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
     let mut years = vec![];
-    // get articles by tag and map them by year.
+    let timezone = state.config.timezone();
+    // get articles by tag and map them by the year in the configured timezone.
     let articles_by_year = Article::get_by_tag(&state.db, &tag).await.into_iter().fold(
         HashMap::new(),
         |mut acc, article| {
-            let year = article.created_at.year();
+            let year = article.created_at.with_timezone(&timezone).year();
             acc.entry(year)
                 .or_insert_with(|| {
                     years.push(year);
@@ -115,55 +173,87 @@ pub async fn handler_tag(
         },
     );
     if articles_by_year.is_empty() {
-        return handler_404(State(state)).await;
+        return Err(Error::NotFound);
     }
     // sort `years` in descending order.
     years.sort_by(|a, b| b.cmp(a));
-    Ok(render_template_with_context!(
-        state,
-        "tag.html",
-        context! {
-            tag => tag,
-            years => years,
-            articles_by_year => articles_by_year,
-        },
+    Ok(validators.attach(
+        render_template_with_context!(
+            state,
+            "tag.html",
+            context! {
+                tag => tag,
+                years => years,
+                articles_by_year => articles_by_year,
+            },
+        ),
+        state.config.pages_cache_control(),
     ))
 }
 
-pub async fn handler_404(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
-    handler_error(
-        State(state),
-        Some("404".to_string()),
-        Some("Oops, page not found...".to_string()),
-    )
-    .await
+pub async fn handler_404() -> Error {
+    Error::NotFound
 }
 
+// Render the error page for the responses converted from `Error`, so that the handlers can simply
+// return the errors without rendering the page by themselves.
 pub async fn handler_error(
     State(state): State<Arc<AppState>>,
-    title: Option<String>,
-    message: Option<String>,
-) -> Result<Html<String>, StatusCode> {
-    Ok(render_template_with_context!(
-        state,
-        "error.html",
-        context! {
-            title => title.unwrap_or("Error".to_string()),
-            message => message.unwrap_or("Oops, it seems like something went wrong...".to_string()),
-        },
-    ))
+    request: Request,
+    next: Next,
+) -> Response {
+    // keep the request id to be shown on the error page for tracing the logs.
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let response = next.run(request).await;
+    let error_page = match response.extensions().get::<ErrorPage>() {
+        Some(error_page) => error_page.clone(),
+        None => return response,
+    };
+    let rendered = state
+        .render_template(
+            "error.html",
+            context! {
+                title => error_page.title,
+                message => state.i18n.t(&error_page.message_key, &[]),
+                request_id => request_id,
+            },
+        )
+        .await;
+    match rendered {
+        Ok(rendered) => (response.status(), Html(rendered)).into_response(),
+        Err(err) => {
+            // fall back to the plain text if the error page itself fails to render.
+            error!("failed rendering the error page: {:?}", err);
+            (
+                response.status(),
+                state.i18n.t(&error_page.message_key, &[]),
+            )
+                .into_response()
+        }
+    }
 }
+
 pub async fn handler_articles(
     State(state): State<Arc<AppState>>,
-) -> Result<Html<String>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
     let mut years = vec![];
-    // get all articles and map them by year.
+    let timezone = state.config.timezone();
+    // get all articles and map them by the year in the configured timezone.
     let articles_by_year =
         Article::get_all(&state.db)
             .await
             .into_iter()
             .fold(HashMap::new(), |mut acc, article| {
-                let year = article.created_at.year();
+                let year = article.created_at.with_timezone(&timezone).year();
                 acc.entry(year)
                     .or_insert_with(|| {
                         years.push(year);
@@ -173,56 +263,211 @@ pub async fn handler_articles(
                 acc
             });
 
-    Ok(render_template_with_context!(
-        state,
-        "articles.html",
-        context! {
-            years => years,
-            articles_by_year => articles_by_year,
-        },
+    Ok(validators.attach(
+        render_template_with_context!(
+            state,
+            "articles.html",
+            context! {
+                years => years,
+                articles_by_year => articles_by_year,
+            },
+        ),
+        state.config.pages_cache_control(),
     ))
 }
 
-pub async fn handler_tags(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
-    Ok(render_template_with_context!(
-        state,
-        "tags.html",
-        context! {tags => Tags::get_all_with_count(&state.db).await},
+pub async fn handler_tags(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
+    Ok(validators.attach(
+        render_template_with_context!(
+            state,
+            "tags.html",
+            context! {tags => Tags::get_all_with_count(&state.db).await},
+        ),
+        state.config.pages_cache_control(),
     ))
 }
 
 pub async fn handler_custom_page(
     State(state): State<Arc<AppState>>,
     Path(title): Path<String>,
-) -> Result<Html<String>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, Error> {
     let page = match Page::get_by_title(&state.db, &title).await {
         Some(page) => page,
-        None => return handler_404(State(state)).await,
+        None => return Err(Error::NotFound),
     };
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
 
-    Ok(render_template_with_context!(
-        state,
-        "page.html",
-        context! {page => page},
+    Ok(validators.attach(
+        render_template_with_context!(state, "page.html", context! {page => page},),
+        state.config.pages_cache_control(),
     ))
 }
 
-pub async fn handler_feed(State(state): State<Arc<AppState>>) -> Response<Body> {
-    let mut response = Response::new(Body::new(
-        render_template_with_context!(
-            state,
-            "feed.xml",
-            context! {
-                updated_at => Article::get_latest_updated(&state.db).await,
-                articles => Article::get_all(&state.db).await,
-            },
-        )
-        .0,
-    ));
-    response
-        .headers_mut()
-        .insert(CONTENT_TYPE, "text/xml; charset=utf-8".parse().unwrap());
-    response
+pub async fn handler_feed(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    render_feed(
+        &state,
+        &headers,
+        "feed.xml",
+        "application/atom+xml; charset=utf-8",
+    )
+    .await
+}
+
+pub async fn handler_json_feed(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    render_feed(
+        &state,
+        &headers,
+        "feed.json",
+        "application/feed+json; charset=utf-8",
+    )
+    .await
+}
+
+pub async fn handler_rss(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    render_feed(
+        &state,
+        &headers,
+        "rss.xml",
+        "application/rss+xml; charset=utf-8",
+    )
+    .await
+}
+
+pub async fn handler_tag_feed(
+    State(state): State<Arc<AppState>>,
+    Path(tag): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.feed_cache_control()) {
+        return Ok(response);
+    }
+    let mut articles = Article::get_by_tag(&state.db, &tag).await;
+    if articles.is_empty() {
+        return Err(Error::NotFound);
+    }
+    let limit = state.config.feed_limit() as usize;
+    if limit > 0 {
+        articles.truncate(limit);
+    }
+    let updated_at = articles.iter().map(|article| article.updated_at).max();
+    // the excerpts are only used as the summaries without the full content.
+    if !state.config.feed_full_content() {
+        state.fill_excerpts(&mut articles);
+    }
+
+    Ok(validators.attach(
+        (
+            [(CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            render_template_with_context!(
+                state,
+                "feed.xml",
+                context! {
+                    tag => tag,
+                    updated_at => updated_at,
+                    articles => articles,
+                },
+            )
+            .0,
+        ),
+        state.config.feed_cache_control(),
+    ))
+}
+
+async fn render_feed(
+    state: &AppState,
+    headers: &HeaderMap,
+    template_name: &str,
+    content_type: &'static str,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(headers, state.config.feed_cache_control()) {
+        return Ok(response);
+    }
+    let mut articles = match state.config.feed_limit() {
+        0 => Article::get_all(&state.db).await,
+        limit => Article::get_on_page(&state.db, 1, limit).await,
+    };
+    // the excerpts are only used as the summaries without the full content.
+    if !state.config.feed_full_content() {
+        state.fill_excerpts(&mut articles);
+    }
+
+    Ok(validators.attach(
+        (
+            [(CONTENT_TYPE, content_type)],
+            render_template_with_context!(
+                state,
+                template_name,
+                context! {
+                    updated_at => Article::get_latest_updated(&state.db).await,
+                    articles => articles,
+                },
+            )
+            .0,
+        ),
+        state.config.feed_cache_control(),
+    ))
+}
+
+pub async fn handler_sitemap(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let validators = state.site_validators().await;
+    if let Some(response) = validators.check(&headers, state.config.pages_cache_control()) {
+        return Ok(response);
+    }
+    let total_article_count = Article::get_total_count(&state.db).await as u32;
+    let max_page =
+        (total_article_count as f32 / state.config.article_per_page() as f32).ceil() as u32;
+
+    Ok(validators.attach(
+        (
+            [(CONTENT_TYPE, "application/xml; charset=utf-8")],
+            render_template_with_context!(
+                state,
+                "sitemap.xml",
+                context! {
+                    updated_at => Article::get_latest_updated(&state.db).await,
+                    max_page => max_page,
+                    articles => Article::get_all(&state.db).await,
+                    pages => Page::get_all(&state.db).await,
+                    tags => Tags::get_all_with_count(&state.db).await,
+                },
+            )
+            .0,
+        ),
+        state.config.pages_cache_control(),
+    ))
+}
+
+pub async fn handler_robots(State(state): State<Arc<AppState>>) -> Result<Response, Error> {
+    Ok((
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        render_template_with_context!(state, "robots.txt").0,
+    )
+        .into_response())
 }
 
 #[derive(Deserialize)]
@@ -233,7 +478,7 @@ pub struct LoginQuery {
 pub async fn handler_login_get(
     State(state): State<Arc<AppState>>,
     Query(query): Query<LoginQuery>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, Error> {
     Ok(render_template_with_context!(
         state,
         "login.html",
@@ -261,11 +506,11 @@ pub async fn handler_login_post(
 
             return Redirect::to(&login_url).into_response();
         }
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(err) => return Error::Authentication(err.to_string()).into_response(),
     };
     // login the user into the session.
-    if auth_session.login(&user).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    if let Err(err) = auth_session.login(&user).await {
+        return Error::Authentication(err.to_string()).into_response();
     }
     // redirect to the next page if it exists.
     if let Some(ref next) = credentials.next {
@@ -279,7 +524,7 @@ pub async fn handler_login_post(
 pub async fn handler_logout(mut auth_session: AuthSession<AppState>) -> impl IntoResponse {
     match auth_session.logout().await {
         Ok(_) => Redirect::to("/").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(err) => Error::Authentication(err.to_string()).into_response(),
     }
 }
 
@@ -291,7 +536,7 @@ pub struct AdminQuery {
 pub async fn handler_admin(
     State(state): State<Arc<AppState>>,
     Query(admin_query): Query<AdminQuery>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, Error> {
     Ok(render_template_with_context!(
         state,
         "admin.html",
@@ -303,6 +548,111 @@ pub async fn handler_admin(
     ))
 }
 
+pub async fn handler_media_library(
+    State(state): State<Arc<AppState>>,
+    Query(admin_query): Query<AdminQuery>,
+) -> Result<Html<String>, Error> {
+    Ok(render_template_with_context!(
+        state,
+        "media.html",
+        context! {
+            message => admin_query.message,
+            media_files => MediaFile::get_all(&state.db).await,
+            max_size_mb => state.config.media_max_size() / 1024 / 1024,
+            allowed_types => state.config.media_allowed_types(),
+        },
+    ))
+}
+
+pub async fn handler_media_upload_post(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut uploaded = 0;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => {
+                error!("failed reading the multipart upload: {:?}", err);
+                return media_upload_failed(&state, &upload_error(err));
+            }
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        let name = field.file_name().unwrap_or_default().to_string();
+        let bytes = match field.bytes().await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("failed reading the uploaded media {}: {:?}", name, err);
+                return media_upload_failed(&state, &upload_error(err));
+            }
+        };
+        // the file input submits an empty part if nothing is chosen.
+        if name.is_empty() && bytes.is_empty() {
+            continue;
+        }
+        match save_media(&state, &name, &bytes).await {
+            Ok(media_file) => {
+                info!("uploaded media {} as {}", name, media_file.path);
+                uploaded += 1;
+            }
+            Err(err) => {
+                error!("failed saving the uploaded media {}: {:?}", name, err);
+                return media_upload_failed(&state, &err);
+            }
+        }
+    }
+    if uploaded == 0 {
+        return redirect_with_message(MEDIA_LIBRARY_URL, &state.i18n.t("media.no_file", &[]))
+            .into_response();
+    }
+    Redirect::to(MEDIA_LIBRARY_URL).into_response()
+}
+
+// Treat the body exceeding the limit as a too large media file.
+fn upload_error(err: MultipartError) -> Error {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        Error::MediaTooLarge(0)
+    } else {
+        Error::BadRequest(err.body_text())
+    }
+}
+
+fn media_upload_failed(state: &AppState, err: &Error) -> Response {
+    let message = match err {
+        Error::MediaTooLarge(_) => state.i18n.t(
+            "media.too_large",
+            &[(
+                "max_size",
+                &(state.config.media_max_size() / 1024 / 1024).to_string(),
+            )],
+        ),
+        Error::MediaTypeNotAllowed(mime_type) => state
+            .i18n
+            .t("media.type_not_allowed", &[("type", mime_type)]),
+        _ => state.i18n.t("media.upload_failed", &[]),
+    };
+    redirect_with_message(MEDIA_LIBRARY_URL, &message).into_response()
+}
+
+pub async fn handler_media_delete(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Redirect, Error> {
+    let media_file = MediaFile::get_by_id(&state.db, id)
+        .await
+        .ok_or(Error::NotFound)?;
+    Ok(match delete_media(&state, &media_file).await {
+        Ok(()) => Redirect::to(MEDIA_LIBRARY_URL),
+        Err(err) => {
+            error!("failed deleting media {}: {:?}", media_file.path, err);
+            redirect_with_message(MEDIA_LIBRARY_URL, &state.i18n.t("admin.delete_failed", &[]))
+        }
+    })
+}
+
 #[derive(Deserialize)]
 pub struct ChangePasswordQuery {
     message: Option<String>,
@@ -311,7 +661,7 @@ pub struct ChangePasswordQuery {
 pub async fn handler_change_pw_get(
     State(state): State<Arc<AppState>>,
     Query(change_pw_query): Query<ChangePasswordQuery>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, Error> {
     Ok(render_template_with_context!(
         state,
         "change_pw.html",
@@ -349,7 +699,7 @@ pub async fn handler_change_pw_post(
         _ => {
             return redirect_with_message(
                 CHANGE_PW_URL,
-                "Failed to validate the old password, please try again.",
+                &state.i18n.t("admin.old_password_invalid", &[]),
             )
             .into_response();
         }
@@ -366,30 +716,46 @@ pub async fn handler_change_pw_post(
         Ok(_) => Redirect::to(ADMIN_URL),
         Err(_) => redirect_with_message(
             CHANGE_PW_URL,
-            "Failed to update the password, please try again.",
+            &state.i18n.t("admin.change_password_failed", &[]),
         ),
     }
     .into_response()
 }
 
 fn redirect_with_message(url: &str, message: &str) -> Redirect {
-    Redirect::to(format!("{}?message={}", url, message).as_str())
+    // encode the message since it may contain the non-ASCII characters after translated.
+    Redirect::to(
+        format!(
+            "{}?message={}",
+            url,
+            utf8_percent_encode(message, NON_ALPHANUMERIC)
+        )
+        .as_str(),
+    )
 }
 
 pub async fn handler_edit_article_get(
     State(state): State<Arc<AppState>>,
+    auth_session: AuthSession<AppState>,
     Path(editor_path): Path<EditorPath>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, Error> {
     let article = match editor_path.id {
-        Some(id) => Article::get_by_id(&state.db, id).await,
+        // editing a non-existent one is a 404 rather than creating a new one.
+        Some(id) => Some(
+            Article::get_by_id(&state.db, id)
+                .await
+                .ok_or(Error::NotFound)?,
+        ),
         None => None,
     };
+    let autosave = newer_autosave(&state, &auth_session, article.as_ref()).await;
 
     Ok(render_template_with_context!(
         state,
         "editor.html",
         context! {
             article => article,
+            autosave => autosave,
             is_page => false,
         },
     ))
@@ -397,25 +763,51 @@ pub async fn handler_edit_article_get(
 
 pub async fn handler_edit_page_get(
     State(state): State<Arc<AppState>>,
+    auth_session: AuthSession<AppState>,
     Path(editor_path): Path<EditorPath>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, Error> {
     let page = match editor_path.id {
-        Some(id) => Page::get_by_id(&state.db, id).await,
+        // editing a non-existent one is a 404 rather than creating a new one.
+        Some(id) => Some(
+            Page::get_by_id(&state.db, id)
+                .await
+                .ok_or(Error::NotFound)?,
+        ),
         None => None,
     };
+    let autosave = newer_autosave(&state, &auth_session, page.as_ref()).await;
 
     Ok(render_template_with_context!(
         state,
         "editor.html",
         context! {
             article => page,
+            autosave => autosave,
             is_page => true,
         },
     ))
 }
 
+// Get the autosave of the current user to offer restoring.
+async fn newer_autosave<T: Editable>(
+    state: &AppState,
+    auth_session: &AuthSession<AppState>,
+    entity: Option<&T>,
+) -> Option<Autosave> {
+    let user = auth_session.user.as_ref()?;
+    let autosave = Autosave::get(
+        &state.db,
+        &user.username,
+        T::KIND,
+        entity.and_then(|entity| entity.id()),
+    )
+    .await?;
+    autosave.is_newer_than(entity).then_some(autosave)
+}
+
 pub async fn handler_edit_post<T: Editable>(
     State(state): State<Arc<AppState>>,
+    auth_session: AuthSession<AppState>,
     Entity { entity, is_new }: Entity<T>,
 ) -> impl IntoResponse {
     let result = if is_new {
@@ -427,22 +819,43 @@ pub async fn handler_edit_post<T: Editable>(
     };
 
     match result {
-        Ok(output) => Redirect::to(T::get_redirect_url(&output).as_str()),
+        Ok(output) => {
+            // the autosave is submitted now, so it's no longer needed.
+            if let Some(user) = &auth_session.user {
+                if let Err(err) =
+                    Autosave::delete(&state.db, &user.username, T::KIND, entity.id()).await
+                {
+                    error!("failed deleting the autosave of {}: {:?}", entity, err);
+                }
+            }
+            Redirect::to(T::get_redirect_url(&output).as_str())
+        }
+        // show both versions to let the user merge or overwrite the concurrent edits.
+        Err(Error::EditConflict(id)) => {
+            info!("conflicted updating {}", entity);
+            let latest = match T::get_latest(&state.db, id).await {
+                Some(latest) => latest,
+                None => return Error::NotFound.into_response(),
+            };
+            return match state
+                .render_template("conflict.html", context! {mine => entity, latest => latest})
+                .await
+            {
+                Ok(rendered) => (StatusCode::CONFLICT, Html(rendered)).into_response(),
+                Err(err) => err.into_response(),
+            };
+        }
         Err(err) => {
             error!("failed processing {}: {:?}", entity, err);
             match err {
                 Error::PageTitleExists(title) => redirect_with_message(
                     ADMIN_URL,
-                    &format!(
-                        "Page with title '{}' (whose URL is also '/{}') already exists.",
-                        title,
-                        title.to_lowercase()
+                    &state.i18n.t(
+                        "admin.page_title_exists",
+                        &[("title", &title), ("url", &title.to_lowercase())],
                     ),
                 ),
-                _ => redirect_with_message(
-                    ADMIN_URL,
-                    "Failed to save the changes, please try again.",
-                ),
+                _ => redirect_with_message(ADMIN_URL, &state.i18n.t("admin.save_failed", &[])),
             }
         }
     }
@@ -458,12 +871,70 @@ pub async fn handler_delete_post<T: Editable>(
         Ok(()) => Redirect::to(ADMIN_URL),
         Err(err) => {
             error!("failed deleting {}: {:?}", entity, err);
-            redirect_with_message(ADMIN_URL, "Failed to delete, please try again.")
+            redirect_with_message(ADMIN_URL, &state.i18n.t("admin.delete_failed", &[]))
         }
     }
     .into_response()
 }
 
+#[derive(Deserialize)]
+pub struct AutosaveForm {
+    title: Option<String>,
+    tags: Option<String>,
+    content: Option<String>,
+}
+
+pub async fn handler_autosave_post<T: Editable>(
+    State(state): State<Arc<AppState>>,
+    auth_session: AuthSession<AppState>,
+    Path(editor_path): Path<EditorPath>,
+    Form(autosave_form): Form<AutosaveForm>,
+) -> Result<StatusCode, Error> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| Error::Authentication("not logged in".to_string()))?;
+    Autosave::save(
+        &state.db,
+        &user.username,
+        T::KIND,
+        editor_path.id,
+        autosave_form.title.as_deref().unwrap_or_default(),
+        autosave_form.tags.as_deref().unwrap_or_default(),
+        autosave_form.content.as_deref().unwrap_or_default(),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn handler_autosave_discard_post<T: Editable>(
+    State(state): State<Arc<AppState>>,
+    auth_session: AuthSession<AppState>,
+    Path(editor_path): Path<EditorPath>,
+) -> Result<StatusCode, Error> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| Error::Authentication("not logged in".to_string()))?;
+    Autosave::delete(&state.db, &user.username, T::KIND, editor_path.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct PreviewForm {
+    content: Option<String>,
+}
+
+pub async fn handler_preview_post(
+    State(state): State<Arc<AppState>>,
+    Form(preview_form): Form<PreviewForm>,
+) -> Html<String> {
+    // render with the same pipeline as the `md_to_html` filter used by the published pages.
+    Html(md_to_html(
+        &state.config,
+        &state.images,
+        preview_form.content.as_deref().unwrap_or_default(),
+    ))
+}
+
 pub async fn handler_ping(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     // Check if the database connection is alive.
     if state.db.is_closed() {
@@ -481,3 +952,296 @@ pub async fn handler_ping(State(state): State<Arc<AppState>>) -> impl IntoRespon
     }
     (StatusCode::OK, "pong".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::config::tests::test_config;
+
+    #[tokio::test]
+    async fn test_render_feeds() {
+        let state = AppState::for_test(test_config("", ""));
+        let created_at = "2024-08-22T01:06:04Z".parse().unwrap();
+        let article = Article {
+            id: Some(1),
+            title: "Tom & \"Jerry\"".to_string(),
+            content: "`a]]>b` &lt;c".to_string(),
+            tags: "foo, bar".to_string(),
+            created_at,
+            updated_at: created_at,
+            ..Default::default()
+        };
+        let context = context! {articles => vec![&article], updated_at => created_at};
+
+        let json = state
+            .render_template("feed.json", context.clone())
+            .await
+            .unwrap();
+        let feed = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(feed["feed_url"], "https://example.com/feed.json");
+        let item = &feed["items"][0];
+        assert_eq!(item["url"], "https://example.com/article/1");
+        assert_eq!(item["title"], "Tom & \"Jerry\"");
+        assert_eq!(item["content_html"], "<p><code>a]]&gt;b</code> &lt;c</p>\n");
+        assert_eq!(item["date_published"], "2024-08-22T01:06:04+00:00");
+        assert_eq!(item["tags"], serde_json::json!(["foo", "bar"]));
+
+        let rss = state.render_template("rss.xml", context).await.unwrap();
+        // the feed stays well-formed with `]]>` in the content.
+        let mut reader = quick_xml::Reader::from_str(&rss);
+        let mut descriptions = vec![];
+        let mut in_description = false;
+        loop {
+            match reader.read_event().unwrap() {
+                quick_xml::events::Event::Start(start) => {
+                    in_description = start.name().as_ref() == b"description"
+                }
+                quick_xml::events::Event::Text(text) if in_description => {
+                    descriptions.push(text.unescape().unwrap().into_owned())
+                }
+                quick_xml::events::Event::End(_) => in_description = false,
+                quick_xml::events::Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            descriptions,
+            ["rsomhaP", "<p><code>a]]&gt;b</code> &lt;c</p>\n"]
+        );
+        assert!(rss.contains("<title>Tom &amp; &quot;Jerry&quot;</title>"));
+        assert!(rss.contains("<pubDate>Thu, 22 Aug 2024 01:06:04 +0000</pubDate>"));
+        assert!(rss.contains("<category>bar</category>"));
+    }
+
+    #[tokio::test]
+    async fn test_render_tag_feed_with_summaries() {
+        let state = AppState::for_test(test_config("", "[feed]\nfull_content = false"));
+        let created_at = "2024-08-22T01:06:04Z".parse().unwrap();
+        let mut articles = vec![Article {
+            id: Some(1),
+            title: "Hello".to_string(),
+            content: "Intro `]]>`\n\n<!--more-->\n\nThe rest".to_string(),
+            created_at,
+            updated_at: created_at,
+            ..Default::default()
+        }];
+        state.fill_excerpts(&mut articles);
+        let atom = state
+            .render_template(
+                "feed.xml",
+                context! {tag => "web dev", updated_at => created_at, articles => articles},
+            )
+            .await
+            .unwrap();
+        let mut reader = quick_xml::Reader::from_str(&atom);
+        while reader.read_event().unwrap() != quick_xml::events::Event::Eof {}
+        let atom = quick_xml::escape::unescape(&atom).unwrap();
+        assert!(atom.contains("<title>rsomhaP | web dev</title>"));
+        assert!(atom.contains(r#"<link href="https://example.com/tag/web%20dev/feed" rel="self""#));
+        // only the excerpt is included without the full content.
+        assert!(
+            atom.contains("<summary type=\"html\"><p>Intro <code>]]&gt;</code></p>\n</summary>")
+        );
+        assert!(!atom.contains("<content"));
+        assert!(!atom.contains("The rest"));
+    }
+
+    #[tokio::test]
+    async fn test_render_sitemap_and_robots() {
+        let state = AppState::for_test(test_config("", ""));
+        let updated_at = "2024-08-22T01:06:04Z".parse::<DateTime<Utc>>().unwrap();
+        let sitemap = state
+            .render_template(
+                "sitemap.xml",
+                context! {
+                    updated_at => updated_at,
+                    max_page => 3,
+                    articles => vec![context! {id => 1, updated_at => updated_at}],
+                    pages => vec![context! {title => "About Me", updated_at => updated_at}],
+                    tags => vec![context! {name => "web dev"}],
+                },
+            )
+            .await
+            .unwrap();
+        let mut reader = quick_xml::Reader::from_str(&sitemap);
+        let mut locs = vec![];
+        let mut in_loc = false;
+        loop {
+            match reader.read_event().unwrap() {
+                quick_xml::events::Event::Start(start) => in_loc = start.name().as_ref() == b"loc",
+                quick_xml::events::Event::Text(text) if in_loc => {
+                    locs.push(text.unescape().unwrap().into_owned())
+                }
+                quick_xml::events::Event::End(_) => in_loc = false,
+                quick_xml::events::Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            locs,
+            [
+                "https://example.com/",
+                "https://example.com/page/2",
+                "https://example.com/page/3",
+                "https://example.com/articles",
+                "https://example.com/tags",
+                "https://example.com/article/1",
+                "https://example.com/about%20me",
+                "https://example.com/tag/web%20dev",
+            ]
+        );
+        assert!(sitemap.contains("<lastmod>2024-08-22T01:06:04Z</lastmod>"));
+
+        let robots = state
+            .render_template("robots.txt", context! {})
+            .await
+            .unwrap();
+        assert_eq!(
+            robots,
+            "User-agent: *\nDisallow: /admin\nDisallow: /login\n\nSitemap: https://example.com/sitemap.xml"
+        );
+        let state = AppState::for_test(test_config("", "[robots]\ndisallow = []"));
+        let robots = state
+            .render_template("robots.txt", context! {})
+            .await
+            .unwrap();
+        assert!(!robots.contains("Disallow"));
+    }
+
+    #[tokio::test]
+    async fn test_render_conflict() {
+        let state = AppState::for_test(test_config("", ""));
+        let latest = Article {
+            id: Some(1),
+            version: 3,
+            ..Default::default()
+        };
+        let mine = Article {
+            title: "Hello".to_string(),
+            tags: "foo, bar".to_string(),
+            content: "<b>there</b>".to_string(),
+            new_created_at: "2024-08-22T09:30:00Z".parse().ok(),
+            cover_image: Some("cover.png".to_string()),
+            summary: Some(String::new()),
+            new_hide_toc: Some(true),
+            keep_updated_at: true,
+            ..Default::default()
+        };
+        let rendered = state
+            .render_template("conflict.html", context! {mine => mine, latest => &latest})
+            .await
+            .unwrap();
+        // overwriting bases on the latest version and carries every submitted field.
+        assert!(rendered.contains(r#"<input name="version" type="hidden" value="3">"#));
+        assert!(rendered.contains(r#"value="2024-08-22T09:30:00">"#));
+        assert!(rendered
+            .contains(r#"name="cover_image" type="text" style="width:100%;" value="cover.png">"#));
+        assert!(rendered.contains(r#"name="summary"></textarea>"#));
+        assert!(rendered.contains(r#"<option value="true" selected>"#));
+        assert!(rendered.contains(r#"type="checkbox" checked>"#));
+        assert!(rendered.contains("&lt;b&gt;there&lt;&#x2f;b&gt;</textarea>"));
+
+        // the fields absent from the submission are still absent, i.e. unchanged.
+        let mine = Article {
+            title: "Hello".to_string(),
+            ..Default::default()
+        };
+        let rendered = state
+            .render_template("conflict.html", context! {mine => mine, latest => latest})
+            .await
+            .unwrap();
+        for name in ["created_at", "cover_image", "summary", "hide_toc"] {
+            assert!(!rendered.contains(&format!(r#"name="{}""#, name)));
+        }
+        assert!(!rendered.contains("checked"));
+    }
+
+    #[tokio::test]
+    async fn test_render_editor_with_autosave() {
+        let state = AppState::for_test(test_config("", ""));
+        let autosave = Autosave {
+            title: "Draft".to_string(),
+            tags: String::new(),
+            content: "</script><script>alert(1)</script>".to_string(),
+            saved_at: "2024-08-22T01:06:04Z".parse().unwrap(),
+        };
+        let render = |autosave: Option<Autosave>| {
+            state.render_template(
+                "editor.html",
+                context! {article => Article::default(), autosave => autosave, is_page => false},
+            )
+        };
+        let rendered = render(Some(autosave)).await.unwrap();
+        assert!(rendered.contains("There are unsaved changes autosaved at 2024-08-22 01:06:04."));
+        // the autosaved content can't break out of the script.
+        assert!(!rendered.contains("<script>alert(1)"));
+        assert!(!render(None).await.unwrap().contains("autosave_notice"));
+    }
+
+    #[tokio::test]
+    async fn test_handler_preview_post() {
+        let state = Arc::new(AppState::for_test(test_config(
+            "",
+            "[markdown]\nmath = \"mathml\"",
+        )));
+        let content = "## Hello\n\n```rust\nfn main() {}\n```\n\n$x^2$";
+        let Html(preview) = handler_preview_post(
+            State(state.clone()),
+            Form(PreviewForm {
+                content: Some(content.to_string()),
+            }),
+        )
+        .await;
+        // the same output as the published pages, including the highlighted code and the math.
+        assert_eq!(preview, md_to_html(&state.config, &state.images, content));
+        assert!(preview.contains(r#"id="h-hello""#));
+        assert!(preview.contains(r#"<pre style="background-color:"#));
+        assert!(preview.contains("<math"));
+
+        let Html(preview) =
+            handler_preview_post(State(state), Form(PreviewForm { content: None })).await;
+        assert_eq!(preview, "");
+    }
+
+    #[test]
+    fn test_social_image() {
+        let config = test_config("", "");
+        let mut article = Article {
+            content: "Hi\n\n![a](/media/a.png \"A\")\n\n![b](https://example.com/b.png)"
+                .to_string(),
+            cover_image: Some("/media/cover.png".to_string()),
+            ..Default::default()
+        };
+        // the cover image comes first.
+        assert_eq!(
+            social_image(&config, &article, 1).as_deref(),
+            Some("https://example.com/media/cover.png")
+        );
+        // then the first image in the content, an empty cover image is unset.
+        article.cover_image = Some(String::new());
+        assert_eq!(
+            social_image(&config, &article, 1).as_deref(),
+            Some("https://example.com/media/a.png")
+        );
+        // then the generated card.
+        article.content = "Hi".to_string();
+        assert_eq!(
+            social_image(&config, &article, 1).as_deref(),
+            Some("https://example.com/article/1/og.png")
+        );
+
+        // then the default image if the cards are disabled.
+        let config = test_config(
+            r#"default_image = "/static/img/card.png""#,
+            "[og_image]\nenable = false",
+        );
+        assert_eq!(
+            social_image(&config, &article, 1).as_deref(),
+            Some("https://example.com/static/img/card.png")
+        );
+        let config = test_config("", "[og_image]\nenable = false");
+        assert_eq!(social_image(&config, &article, 1), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use chrono::{DateTime, Locale, TimeZone};
+use tracing::info;
+
+use crate::{assets::embedded_locale, Error};
+
+pub const DEFAULT_LANG: &str = "en";
+const LOCALES_DIR: &str = "locales";
+
+// The translated messages of the configured language, the missing ones fall back to
+// the default language and then the key itself.
+#[derive(Clone, Debug)]
+pub struct I18n {
+    messages: HashMap<String, String>,
+}
+
+impl I18n {
+    pub fn load(lang: &str) -> Result<Self, Error> {
+        let mut messages = HashMap::new();
+        for lang in [DEFAULT_LANG, lang] {
+            let mut found = false;
+            // the catalog on the disk overrides the embedded one message by message.
+            let disk_catalog = Path::new(LOCALES_DIR).join(format!("{}.toml", lang));
+            for catalog in [
+                embedded_locale(lang)?,
+                if disk_catalog.is_file() {
+                    Some(std::fs::read_to_string(&disk_catalog)?)
+                } else {
+                    None
+                },
+            ]
+            .into_iter()
+            .flatten()
+            {
+                flatten_catalog("", toml::from_str(&catalog)?, &mut messages);
+                found = true;
+            }
+            if !found {
+                return Err(Error::ConfigValidation(format!(
+                    "invalid meta config, no translation catalog found for the language {}",
+                    lang
+                )));
+            }
+        }
+        info!(
+            "loaded {} messages for the language {}",
+            messages.len(),
+            lang
+        );
+
+        Ok(Self { messages })
+    }
+
+    // Translate the message, the `{name}` placeholders are replaced by the arguments.
+    pub fn t(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut message = self
+            .messages
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string());
+        for (name, value) in args {
+            message = message.replace(&format!("{{{}}}", name), value);
+        }
+        message
+    }
+
+    // Format the datetime with the localized format of the style, e.g. `long` for `date.long_format`.
+    // An invalid format in the catalog is an error rather than a panic.
+    pub fn format_date<Tz: TimeZone>(
+        &self,
+        datetime: &DateTime<Tz>,
+        style: Option<&str>,
+    ) -> Result<String, std::fmt::Error>
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        let format_key = match style {
+            Some(style) => format!("date.{}_format", style),
+            None => "date.format".to_string(),
+        };
+        let format = self.t(&format_key, &[]);
+        let locale = self
+            .messages
+            .get("date.locale")
+            .and_then(|locale| Locale::try_from(locale.as_str()).ok())
+            .unwrap_or(Locale::POSIX);
+        let mut formatted = String::new();
+        write!(formatted, "{}", datetime.format_localized(&format, locale))?;
+        Ok(formatted)
+    }
+}
+
+// Flatten the nested tables of the catalog into the dot-separated keys, e.g. `error.not_found`.
+fn flatten_catalog(prefix: &str, table: toml::Table, messages: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten_catalog(&key, table, messages),
+            toml::Value::String(message) => {
+                messages.insert(key, message);
+            }
+            value => {
+                messages.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i18n() {
+        let i18n = I18n::load("zh-CN").unwrap();
+        assert_eq!(i18n.t("nav.articles", &[]), "文章");
+        assert_eq!(i18n.t("not.exist", &[]), "not.exist");
+        assert_eq!(
+            i18n.t("home.page_of", &[("page", "1"), ("max_page", "3")]),
+            "第 1 页，共 3 页"
+        );
+        let datetime = DateTime::parse_from_rfc3339("2024-08-22T01:06:04Z").unwrap();
+        assert_eq!(i18n.format_date(&datetime, None).unwrap(), "2024-08-22");
+        assert_eq!(
+            i18n.format_date(&datetime, Some("long")).unwrap(),
+            "2024年8月22日"
+        );
+
+        let i18n = I18n::load(DEFAULT_LANG).unwrap();
+        assert_eq!(
+            i18n.format_date(&datetime, Some("long")).unwrap(),
+            "August 22, 2024"
+        );
+        assert!(I18n::load("not-exist").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageFormat, ImageReader,
+};
+
+use crate::{models::MediaFile, Error};
+
+// The quality of the resized JPEG variants.
+const JPEG_QUALITY: u8 = 85;
+
+// The dimensions and type of an uploaded image, used to render the responsive images.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: String,
+}
+
+impl ImageInfo {
+    // only the still images are resized, e.g. the animated GIFs are served as they are.
+    pub fn is_resizable(&self) -> bool {
+        matches!(
+            self.mime_type.as_str(),
+            "image/png" | "image/jpeg" | "image/webp"
+        )
+    }
+}
+
+// The infos of the uploaded images by their paths in the media storage, which are kept in memory
+// since the Markdown is rendered synchronously in the templates.
+#[derive(Clone, Debug, Default)]
+pub struct Images(Arc<RwLock<HashMap<String, ImageInfo>>>);
+
+impl Images {
+    pub async fn load(db: &sqlx::MySqlPool) -> Self {
+        let images = Self::default();
+        for media_file in MediaFile::get_all(db).await {
+            images.insert(&media_file);
+        }
+        images
+    }
+
+    pub fn get(&self, path: &str) -> Option<ImageInfo> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .cloned()
+    }
+
+    // record the media file if it's an image with the known dimensions.
+    pub fn insert(&self, media_file: &MediaFile) {
+        if let (Some(width), Some(height)) = (media_file.width, media_file.height) {
+            self.0
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(
+                    media_file.path.clone(),
+                    ImageInfo {
+                        width: width as u32,
+                        height: height as u32,
+                        mime_type: media_file.mime_type.clone(),
+                    },
+                );
+        }
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(path);
+    }
+}
+
+// Read the dimensions from the image header without decoding the whole image.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+// Resize the image down to the width keeping the aspect ratio, it's never upscaled.
+// The WebP encoder only supports the lossless compression, which may be larger than
+// the original format for the photos, so the smaller one of them is returned.
+pub fn resize_image(bytes: &[u8], width: u32, webp: bool) -> Result<Vec<u8>, Error> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let format = reader.format();
+    let image = reader.decode()?;
+    let image = if width < image.width() {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+        image.resize_exact(width, height, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let original = encode_image(&image, format)?;
+    if webp && format != Some(ImageFormat::WebP) {
+        let converted = encode_image(&image, Some(ImageFormat::WebP))?;
+        if converted.len() < original.len() {
+            return Ok(converted);
+        }
+    }
+    Ok(original)
+}
+
+fn encode_image(image: &DynamicImage, format: Option<ImageFormat>) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    match format {
+        Some(ImageFormat::Jpeg) => {
+            // JPEG has no alpha channel.
+            image
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))?;
+        }
+        Some(ImageFormat::WebP) => {
+            image
+                .to_rgba8()
+                .write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?;
+        }
+        _ => {
+            image.write_with_encoder(PngEncoder::new(&mut bytes))?;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resize_image() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(400, 200)
+            .write_with_encoder(PngEncoder::new(&mut png))
+            .unwrap();
+        assert_eq!(image_dimensions(&png), Some((400, 200)));
+
+        let resized = resize_image(&png, 100, false).unwrap();
+        assert_eq!(image_dimensions(&resized), Some((100, 50)));
+        assert_eq!(infer::get(&resized).unwrap().mime_type(), "image/png");
+        // never upscaled.
+        let resized = resize_image(&png, 800, false).unwrap();
+        assert_eq!(image_dimensions(&resized), Some((400, 200)));
+        // the solid image is smaller as the lossless WebP.
+        let resized = resize_image(&png, 100, true).unwrap();
+        assert_eq!(infer::get(&resized).unwrap().mime_type(), "image/webp");
+        assert_eq!(image_dimensions(&resized), Some((100, 50)));
+    }
+}
@@ -1,10 +1,20 @@
 mod app;
+mod assets;
 mod auth;
+mod cache;
 mod config;
 mod error;
 mod handlers;
+mod i18n;
+mod images;
+mod markdown;
+mod media;
+mod micropub;
 mod models;
+mod og_image;
+mod theme;
 mod utils;
+mod xmlrpc;
 
 pub use app::App;
 pub use error::Error;
@@ -5,7 +5,9 @@ use tracing::error;
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let app = match App::new().await {
+    // the dev mode reloads the templates once they change and disables the caching headers.
+    let dev_mode = std::env::args().skip(1).any(|arg| arg == "--dev");
+    let app = match App::new(dev_mode).await {
         Ok(app) => app,
         Err(e) => {
             error!("failed to create app: {}", e);
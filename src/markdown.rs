@@ -0,0 +1,749 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
+
+use comrak::{
+    format_html_with_plugins,
+    nodes::{AstNode, NodeValue},
+    parse_document,
+    plugins::syntect::{SyntectAdapter, SyntectAdapterBuilder},
+    Arena, Options, Plugins,
+};
+use minijinja::HtmlEscape;
+use pulldown_latex::{config::DisplayMode, push_mathml, Parser, RenderConfig, Storage};
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::{
+    config::{Config, MathRenderer},
+    images::{ImageInfo, Images},
+    media::MEDIA_URL_PREFIX,
+};
+
+// The images rendered by comrak, the raw HTML ones are omitted since `unsafe_` is off.
+static IMG_TAG_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<img src="([^"]*)" alt="([^"]*)"((?: title="[^"]*")?) />"#).unwrap()
+});
+
+// The math rendered by the `math_dollars` extension.
+static MATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<span data-math-style="(inline|display)">([^<]*)</span>"#).unwrap()
+});
+
+// The MathML tags with the attributes written by `pulldown_latex`, which doesn't escape the operators
+// and the contexts of the errors, so anything else starting with `<` is escaped.
+static MATHML_TAG_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^</?(?:math|semantics|annotation|mi|mn|mo|ms|mtext|mspace|mrow|mfrac|msqrt|mroot|mstyle|merror|mpadded|mphantom|menclose|msub|msup|msubsup|munder|mover|munderover|mmultiscripts|mprescripts|none|mtable|mtr|mtd)(?:\s+(?:display|mathvariant|stretchy|symmetric|fence|separator|lspace|rspace|minsize|maxsize|largeop|movablelimits|accent|accentunder|style|class|width|height|depth|voffset|columnalign|rowalign|columnspacing|rowspacing|columnlines|rowlines|frame|notation|linethickness|scriptlevel|displaystyle|form|encoding|xmlns)="[^"<>]*")*\s*/?>"#,
+    )
+    .unwrap()
+});
+
+// The syntax highlight adapters by the theme, which are expensive to build with all the syntaxes
+// and themes loaded.
+static SYNTECT_ADAPTERS: LazyLock<Mutex<HashMap<String, Arc<SyntectAdapter>>>> =
+    LazyLock::new(Default::default);
+
+// The HTML block of the separator marking the end of the excerpt.
+static MORE_SEPARATOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*<!--\s*more\s*-->\s*$").unwrap());
+// The text replacing the separator while rendering, which can't come from the content since
+// the parser replaces the NUL characters.
+const MORE_PLACEHOLDER: &str = "\0more\0";
+static HTML_TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+// The prefix of the heading ids, which keeps them from clashing with the ids of the layout,
+// e.g. `#content` or `#toc`.
+const HEADING_ID_PREFIX: &str = "h-";
+// The anchors generated by the `header_ids` extension, whose links miss the prefix of the ids.
+static HEADING_ANCHOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r##"<a href="#([^"]*)" aria-hidden="true" class="anchor" id="{}"##,
+        HEADING_ID_PREFIX
+    ))
+    .unwrap()
+});
+// The headings with the anchors.
+static HEADING_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r##"<h([1-6])><a href="#[^"]*" aria-hidden="true" class="anchor" id="([^"]*)"></a>(.*?)</h[1-6]>"##,
+    )
+    .unwrap()
+});
+// The paragraph to be replaced with the table of contents.
+static TOC_MARKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<p>\[toc\]</p>\n?").unwrap());
+static TOC_NAV_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?s)<nav class="toc">.*?</nav>\n?"#).unwrap());
+
+// An entry of the table of contents, the subheadings are nested as its children.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub id: String,
+    pub title: String,
+    pub children: Vec<TocEntry>,
+}
+
+// The Markdown content of an article rendered at once for its page.
+pub struct Rendered {
+    // the HTML with the `[toc]` marker replaced, the same as `md_to_html`.
+    pub html: String,
+    // the automatic table of contents.
+    pub toc: Vec<TocEntry>,
+    // the same as `excerpt`.
+    pub excerpt: Excerpt,
+}
+
+// Render the Markdown content once into everything shown on the article page.
+pub fn render_article(config: &Config, images: &Images, md_content: &str) -> Rendered {
+    let (html, more_at) = render(config, images, md_content);
+    let excerpt = match more_at {
+        Some(more_at) => excerpt_before(config, &html[..more_at]),
+        // the one without the separator only takes parsing the content again.
+        None => excerpt(config, images, md_content),
+    };
+    Rendered {
+        toc: auto_toc(config, &html),
+        excerpt,
+        html: replace_toc_marker(config, html),
+    }
+}
+
+// Get the automatic table of contents of the rendered HTML, which is empty if there are
+// too few headings or the content has the `[toc]` marker to show it in place instead.
+fn auto_toc(config: &Config, html: &str) -> Vec<TocEntry> {
+    if !config.toc_enabled() {
+        return Vec::new();
+    }
+    if config.toc_marker_enabled() && TOC_MARKER_RE.is_match(html) {
+        return Vec::new();
+    }
+    let headings = headings(html);
+    if headings.len() < config.toc_min_headings() {
+        return Vec::new();
+    }
+    nest_headings(&headings)
+}
+
+// Get the level, id and text of the headings in the rendered HTML.
+fn headings(html: &str) -> Vec<(u8, String, String)> {
+    HEADING_RE
+        .captures_iter(html)
+        .map(|caps| {
+            (
+                caps[1].parse().unwrap_or(1),
+                caps[2].to_string(),
+                html_to_text(&caps[3]),
+            )
+        })
+        .collect()
+}
+
+// Nest the headings under the preceding ones of the higher levels, the skipped levels are fine.
+fn nest_headings(headings: &[(u8, String, String)]) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    while start < headings.len() {
+        let (level, id, title) = &headings[start];
+        let end = headings[start + 1..]
+            .iter()
+            .position(|(next_level, _, _)| next_level <= level)
+            .map_or(headings.len(), |offset| start + 1 + offset);
+        entries.push(TocEntry {
+            level: *level,
+            id: id.clone(),
+            title: title.clone(),
+            children: nest_headings(&headings[start + 1..end]),
+        });
+        start = end;
+    }
+    entries
+}
+
+fn render_toc_list(entries: &[TocEntry]) -> String {
+    let items = entries
+        .iter()
+        .map(|entry| {
+            let children = if entry.children.is_empty() {
+                String::new()
+            } else {
+                render_toc_list(&entry.children)
+            };
+            format!(
+                r##"<li><a href="#{}">{}</a>{}</li>"##,
+                HtmlEscape(&entry.id),
+                HtmlEscape(&entry.title),
+                children
+            )
+        })
+        .collect::<String>();
+    format!("<ul>{}</ul>", items)
+}
+
+// The beginning of an article shown in the lists and feeds.
+pub struct Excerpt {
+    // the rendered HTML of the excerpt.
+    pub html: String,
+    // the plain text of the excerpt, e.g. for the meta descriptions.
+    pub text: String,
+    // whether the article continues after the excerpt.
+    pub more: bool,
+}
+
+// Get the excerpt of the Markdown content, which is the part before the `<!--more-->` separator,
+// otherwise the text truncated at a word boundary so that no Markdown syntax is cut off. Since it's
+// called for every article in the lists, only the part before the separator is rendered and the
+// text is taken from the parsed nodes without rendering them.
+pub fn excerpt(config: &Config, images: &Images, md_content: &str) -> Excerpt {
+    let options = options(config);
+    let arena = Arena::new();
+    let root = parse_document(&arena, md_content, &options);
+    match more_separator(root) {
+        Some(separator) => {
+            for node in separator.following_siblings().collect::<Vec<_>>() {
+                node.detach();
+            }
+            excerpt_before(config, &format_html(config, images, root, &options))
+        }
+        None => {
+            let mut text = String::new();
+            push_text(config, root, &mut text);
+            let (text, more) = truncate_at_word(
+                &text.split_whitespace().collect::<Vec<_>>().join(" "),
+                config.excerpt_length(),
+            );
+            let html = if text.is_empty() {
+                String::new()
+            } else {
+                format!("<p>{}</p>", HtmlEscape(&text))
+            };
+            Excerpt { html, text, more }
+        }
+    }
+}
+
+// Collect the plain text of the parsed nodes without rendering them, the same as the text of the
+// rendered HTML except that the math is left as the source.
+fn push_text<'a>(config: &Config, node: &'a AstNode<'a>, text: &mut String) {
+    match &node.data.borrow().value {
+        NodeValue::Text(literal) => text.push_str(literal),
+        NodeValue::Code(code) => text.push_str(&code.literal),
+        NodeValue::Math(math) => text.push_str(&math.literal),
+        NodeValue::CodeBlock(block) => {
+            text.push(' ');
+            text.push_str(&block.literal);
+        }
+        // the raw HTML is omitted and so are the alt texts of the images.
+        NodeValue::HtmlBlock(_) | NodeValue::HtmlInline(_) | NodeValue::Image(_) => {}
+        // the in-place table of contents links to the headings not in the excerpt.
+        NodeValue::Paragraph if config.toc_marker_enabled() && is_toc_marker(node) => {}
+        value => {
+            for child in node.children() {
+                push_text(config, child, text);
+            }
+            if value.block() || matches!(value, NodeValue::SoftBreak | NodeValue::LineBreak) {
+                text.push(' ');
+            }
+        }
+    }
+}
+
+// Whether the paragraph is the `[toc]` marker, which is parsed into several text nodes.
+fn is_toc_marker<'a>(paragraph: &'a AstNode<'a>) -> bool {
+    let mut text = String::new();
+    for child in paragraph.children() {
+        match &child.data.borrow().value {
+            NodeValue::Text(literal) => text.push_str(literal),
+            _ => return false,
+        }
+    }
+    text.eq_ignore_ascii_case("[toc]")
+}
+
+// Get the excerpt from the rendered HTML before the separator.
+fn excerpt_before(config: &Config, html: &str) -> Excerpt {
+    // the in-place table of contents links to the headings not in the excerpt.
+    let html = if config.toc_marker_enabled() {
+        TOC_MARKER_RE.replace_all(html, "").into_owned()
+    } else {
+        html.to_string()
+    };
+    let (text, _) = truncate_at_word(&html_to_text(&html), config.excerpt_length());
+    Excerpt {
+        html,
+        text,
+        more: true,
+    }
+}
+
+// Strip the tags of the rendered HTML into the plain text with the whitespaces collapsed.
+fn html_to_text(html: &str) -> String {
+    let html = TOC_NAV_RE.replace_all(html, " ");
+    let text = unescape_html(&HTML_TAG_RE.replace_all(&html, " "));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// Truncate the text to the max chars at the last word boundary, the texts without any,
+// e.g. the CJK ones, are cut at the max chars. Returns whether the text is truncated.
+fn truncate_at_word(text: &str, max_length: usize) -> (String, bool) {
+    if text.chars().count() <= max_length {
+        return (text.to_string(), false);
+    }
+    let truncated = text.chars().take(max_length).collect::<String>();
+    let next_is_space = text
+        .chars()
+        .nth(max_length)
+        .is_some_and(char::is_whitespace);
+    let truncated = match truncated.rfind(char::is_whitespace) {
+        Some(boundary) if !next_is_space => &truncated[..boundary],
+        _ => truncated.as_str(),
+    };
+    (format!("{}…", truncated.trim_end()), true)
+}
+
+// Render the Markdown content into HTML, this is the only pipeline used by the templates and the preview.
+pub fn md_to_html(config: &Config, images: &Images, md_content: &str) -> String {
+    let (html, _) = render(config, images, md_content);
+    replace_toc_marker(config, html)
+}
+
+// Replace the `[toc]` marker with the table of contents of all the headings.
+fn replace_toc_marker(config: &Config, html: String) -> String {
+    if !config.toc_marker_enabled() || !TOC_MARKER_RE.is_match(&html) {
+        return html;
+    }
+    let toc = format!(
+        r#"<nav class="toc">{}</nav>
+"#,
+        render_toc_list(&nest_headings(&headings(&html)))
+    );
+    TOC_MARKER_RE
+        .replace_all(&html, regex::NoExpand(&toc))
+        .into_owned()
+}
+
+// Render the Markdown content into HTML without the `<!--more-->` separator, also returns where
+// the separator was, i.e. the end of the excerpt.
+fn render(config: &Config, images: &Images, md_content: &str) -> (String, Option<usize>) {
+    let options = options(config);
+    let arena = Arena::new();
+    let root = parse_document(&arena, md_content, &options);
+    if let Some(separator) = more_separator(root) {
+        separator.data.borrow_mut().value = NodeValue::Text(MORE_PLACEHOLDER.to_string());
+    }
+    let html = format_html(config, images, root, &options);
+    match html.find(MORE_PLACEHOLDER) {
+        Some(more_at) => (html.replacen(MORE_PLACEHOLDER, "", 1), Some(more_at)),
+        None => (html, None),
+    }
+}
+
+fn options(config: &Config) -> Options<'static> {
+    // enable the configured extension options.
+    let extensions = config.markdown_extensions();
+    let mut options = Options::default();
+    options.extension.strikethrough = extensions.strikethrough;
+    options.extension.autolink = extensions.autolink;
+    options.extension.table = extensions.tables;
+    options.extension.footnotes = extensions.footnotes;
+    options.extension.tasklist = extensions.tasklist;
+    options.extension.description_lists = extensions.description_lists;
+    options.extension.superscript = extensions.superscript;
+    options.extension.math_dollars = extensions.math != MathRenderer::None;
+    // generate the ids and anchor links of the headings.
+    options.extension.header_ids = Some(HEADING_ID_PREFIX.to_string());
+    options.render.figure_with_caption = true;
+    options
+}
+
+// Find the `<!--more-->` separator, only the one as a block of its own counts, not the one in
+// the code blocks or spans, which would otherwise be rendered as an omitted raw HTML comment.
+fn more_separator<'a>(root: &'a AstNode<'a>) -> Option<&'a AstNode<'a>> {
+    root.children().find(|node| {
+        matches!(&node.data.borrow().value, NodeValue::HtmlBlock(block) if MORE_SEPARATOR_RE.is_match(&block.literal))
+    })
+}
+
+// Format the parsed document into HTML with the code highlighted, the heading anchors fixed,
+// the math rendered and the local images made responsive.
+fn format_html<'a>(
+    config: &Config,
+    images: &Images,
+    root: &'a AstNode<'a>,
+    options: &Options,
+) -> String {
+    // enable the syntax highlight adapter.
+    let adapter = syntect_adapter(&config.code_syntax_highlight_theme());
+    let mut plugins = Plugins::default();
+    plugins.render.codefence_syntax_highlighter = Some(adapter.as_ref());
+
+    let mut html = Vec::new();
+    // writing into the memory never fails.
+    format_html_with_plugins(root, options, &mut html, &plugins).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    let html = HEADING_ANCHOR_RE.replace_all(
+        &html,
+        format!(
+            r##"<a href="#{0}$1" aria-hidden="true" class="anchor" id="{0}"##,
+            HEADING_ID_PREFIX
+        ),
+    );
+    let html = render_math(config.markdown_extensions().math, &html);
+    rewrite_local_images(config, images, &html)
+}
+
+fn syntect_adapter(theme: &str) -> Arc<SyntectAdapter> {
+    SYNTECT_ADAPTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(theme.to_string())
+        .or_insert_with(|| Arc::new(SyntectAdapterBuilder::new().theme(theme).build()))
+        .clone()
+}
+
+// Render the math into MathML, or the delimited markup recognized by the KaTeX auto-render extension.
+fn render_math(renderer: MathRenderer, html: &str) -> String {
+    if renderer == MathRenderer::None {
+        return html.to_string();
+    }
+    MATH_RE
+        .replace_all(html, |caps: &Captures| {
+            let display = &caps[1] == "display";
+            match (renderer, display) {
+                (MathRenderer::KaTeX, false) => {
+                    format!(r#"<span class="math math-inline">\({}\)</span>"#, &caps[2])
+                }
+                (MathRenderer::KaTeX, true) => {
+                    format!(r#"<span class="math math-display">\[{}\]</span>"#, &caps[2])
+                }
+                _ => latex_to_mathml(&unescape_html(&caps[2]), display),
+            }
+        })
+        .into_owned()
+}
+
+// The invalid commands are rendered in red in place, the source is only shown as it is if the rendering fails.
+fn latex_to_mathml(latex: &str, display: bool) -> String {
+    let storage = Storage::new();
+    let parser = Parser::new(latex, &storage);
+    let config = RenderConfig {
+        display_mode: if display {
+            DisplayMode::Block
+        } else {
+            DisplayMode::Inline
+        },
+        ..Default::default()
+    };
+    let mut mathml = String::new();
+    match push_mathml(&mut mathml, parser, config) {
+        Ok(()) => sanitize_mathml(&mathml),
+        Err(_) => format!("<code>{}</code>", HtmlEscape(latex)),
+    }
+}
+
+// Escape the `<` not starting a known MathML tag, e.g. the `<` operator or the raw HTML in the source.
+fn sanitize_mathml(mathml: &str) -> String {
+    let mut sanitized = String::with_capacity(mathml.len());
+    let mut rest = mathml;
+    while let Some(start) = rest.find('<') {
+        sanitized.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match MATHML_TAG_RE.find(rest) {
+            Some(tag) => {
+                sanitized.push_str(tag.as_str());
+                rest = &rest[tag.end()..];
+            }
+            None => {
+                sanitized.push_str("&lt;");
+                rest = &rest[1..];
+            }
+        }
+    }
+    sanitized.push_str(rest);
+    sanitized
+}
+
+// Make the images uploaded to the media library responsive and lazily loaded.
+fn rewrite_local_images(config: &Config, images: &Images, html: &str) -> String {
+    let absolute_prefix = format!(
+        "{}{}/",
+        config.blog_url().trim_end_matches('/'),
+        MEDIA_URL_PREFIX
+    );
+    let relative_prefix = format!("{}/", MEDIA_URL_PREFIX);
+    IMG_TAG_RE
+        .replace_all(html, |caps: &Captures| {
+            let (src, alt, title) = (&caps[1], &caps[2], &caps[3]);
+            let path = match src
+                .strip_prefix(&absolute_prefix)
+                .or_else(|| src.strip_prefix(&relative_prefix))
+            {
+                Some(path) => path,
+                // leave the external images as they are.
+                None => return caps[0].to_string(),
+            };
+            match images.get(path) {
+                Some(info) => responsive_image(config, src, alt, title, &info),
+                None => format!(
+                    r#"<img src="{}" alt="{}"{} loading="lazy" />"#,
+                    src, alt, title
+                ),
+            }
+        })
+        .into_owned()
+}
+
+fn responsive_image(
+    config: &Config,
+    src: &str,
+    alt: &str,
+    title: &str,
+    info: &ImageInfo,
+) -> String {
+    let dimensions = format!(
+        r#"width="{}" height="{}" loading="lazy""#,
+        info.width, info.height
+    );
+    if !info.is_resizable() {
+        return format!(
+            r#"<img src="{}" alt="{}"{} {} />"#,
+            src, alt, title, dimensions
+        );
+    }
+    // the variants narrower than the original, plus the original itself.
+    let mut widths = config
+        .media_image_widths()
+        .iter()
+        .copied()
+        .filter(|width| *width < info.width)
+        .collect::<Vec<_>>();
+    widths.sort_unstable();
+    widths.dedup();
+    let srcset = |webp: bool| {
+        let format = if webp { "&amp;format=webp" } else { "" };
+        // the original is served as it is unless converted to WebP.
+        let original = if webp {
+            format!("{}?format=webp {}w", src, info.width)
+        } else {
+            format!("{} {}w", src, info.width)
+        };
+        widths
+            .iter()
+            .map(|width| format!("{}?w={}{} {}w", src, width, format, width))
+            .chain([original])
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let sizes = config.media_image_sizes();
+    let img = format!(
+        r#"<img src="{}" srcset="{}" sizes="{}" alt="{}"{} {} />"#,
+        src,
+        srcset(false),
+        sizes,
+        alt,
+        title,
+        dimensions
+    );
+    if !config.media_webp_enabled() || info.mime_type == "image/webp" {
+        return img;
+    }
+    format!(
+        r#"<picture><source type="image/webp" srcset="{}" sizes="{}" />{}</picture>"#,
+        srcset(true),
+        sizes,
+        img
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::tests::test_config, models::MediaFile};
+
+    #[test]
+    fn test_md_to_html_with_local_images() {
+        let config = test_config("", "");
+        let images = Images::default();
+        images.insert(&MediaFile {
+            id: 1,
+            path: "photo.jpg".to_string(),
+            name: "photo.jpg".to_string(),
+            mime_type: "image/jpeg".to_string(),
+            size: 1024,
+            width: Some(1000),
+            height: Some(500),
+            created_at: Default::default(),
+        });
+
+        let html = md_to_html(&config, &images, "![A photo](/media/photo.jpg)");
+        assert!(html.contains(r#"<picture><source type="image/webp" srcset="/media/photo.jpg?w=480&amp;format=webp 480w, /media/photo.jpg?w=960&amp;format=webp 960w, /media/photo.jpg?format=webp 1000w""#));
+        assert!(html.contains(r#"srcset="/media/photo.jpg?w=480 480w, /media/photo.jpg?w=960 960w, /media/photo.jpg 1000w""#));
+        assert!(html.contains(r#"alt="A photo" width="1000" height="500" loading="lazy" />"#));
+        // the unknown local images are only lazily loaded and the external ones are untouched.
+        let html = md_to_html(
+            &config,
+            &images,
+            "![](/media/old.png) ![](https://example.com/a.png)",
+        );
+        assert!(html.contains(r#"<img src="/media/old.png" alt="" loading="lazy" />"#));
+        assert!(html.contains(r#"<img src="https://example.com/a.png" alt="" />"#));
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let config = test_config("", "");
+        let images = Images::default();
+
+        let excerpt = excerpt(
+            &config,
+            &images,
+            "The **intro** & more.\n\n<!--more-->\n\nThe rest.",
+        );
+        assert_eq!(
+            excerpt.html,
+            "<p>The <strong>intro</strong> &amp; more.</p>\n"
+        );
+        assert_eq!(excerpt.text, "The intro & more.");
+        assert!(excerpt.more);
+        assert!(!md_to_html(&config, &images, "Intro\n\n<!--more-->\n\nRest").contains("<!--"));
+        // the separator in a code span doesn't count.
+        let excerpt = super::excerpt(&config, &images, "Use `<!--more-->` to split.");
+        assert_eq!(excerpt.html, "<p>Use &lt;!--more--&gt; to split.</p>");
+        assert!(!excerpt.more);
+        // neither does the one in a fenced code block, which is also kept in the published one.
+        let md = "Intro\n\n```html\n<p>Intro</p>\n<!--more-->\n<p>Rest</p>\n```\n\nEnd";
+        let excerpt = super::excerpt(&config, &images, md);
+        assert!(!excerpt.more);
+        assert!(excerpt.text.contains("Rest"));
+        assert!(md_to_html(&config, &images, md).contains("more--"));
+        // only the first separator counts.
+        let excerpt = super::excerpt(
+            &config,
+            &images,
+            "A\n\n<!-- more -->\n\nB\n<!--more-->\n\nC",
+        );
+        assert_eq!(excerpt.html, "<p>A</p>\n");
+        // the code before the separator is still highlighted like on the article page.
+        let md = "```rust\nfn main() {}\n```\n\n<!--more-->\n\n```rust\nfn rest() {}\n```";
+        let excerpt = super::excerpt(&config, &images, md);
+        assert!(excerpt.html.starts_with(r#"<pre style="background-color:"#));
+        assert!(!excerpt.html.contains("rest"));
+        assert_eq!(
+            excerpt.html,
+            render_article(&config, &images, md).excerpt.html
+        );
+
+        // the text of the content without the separator, with the marker, the raw HTML and the
+        // alt texts of the images left out.
+        let md = "[toc]\n\n## The *intro*\n\nSee `code` ![alt](/a.png)<b>and</b>\nmore.\n\n```\nfn main() {}\n```";
+        let excerpt = super::excerpt(&config, &images, md);
+        assert_eq!(excerpt.text, "The intro See code and more. fn main() {}");
+        assert_eq!(excerpt.html, format!("<p>{}</p>", excerpt.text));
+        assert!(!excerpt.more);
+        assert_eq!(
+            excerpt.text,
+            render_article(&config, &images, md).excerpt.text
+        );
+        let excerpt = super::excerpt(&config, &images, &"word ".repeat(100));
+        assert_eq!(excerpt.text.chars().count(), 200);
+        assert!(excerpt.text.ends_with("word…"));
+        assert!(excerpt.more);
+
+        assert_eq!(
+            truncate_at_word("hello world", 20),
+            ("hello world".to_string(), false)
+        );
+        assert_eq!(
+            truncate_at_word("hello world", 8),
+            ("hello…".to_string(), true)
+        );
+        assert_eq!(
+            truncate_at_word("hello world", 5),
+            ("hello…".to_string(), true)
+        );
+        assert_eq!(truncate_at_word("你好世界", 2), ("你好…".to_string(), true));
+    }
+
+    #[test]
+    fn test_toc() {
+        let config = test_config("", "");
+        let images = Images::default();
+
+        let md = "## Intro\n\n### `Setup` & run\n\n# Usage\n\n## Intro";
+        let html = md_to_html(&config, &images, md);
+        assert!(html.contains(
+            r##"<h2><a href="#h-intro" aria-hidden="true" class="anchor" id="h-intro"></a>Intro</h2>"##
+        ));
+        // the duplicated headings get the unique ids.
+        assert!(html.contains(r#"id="h-intro-1""#));
+        let entry = |level, id: &str, title: &str, children| TocEntry {
+            level,
+            id: id.to_string(),
+            title: title.to_string(),
+            children,
+        };
+        assert_eq!(
+            render_article(&config, &images, md).toc,
+            vec![
+                entry(
+                    2,
+                    "h-intro",
+                    "Intro",
+                    vec![entry(3, "h-setup--run", "Setup & run", vec![])]
+                ),
+                entry(
+                    1,
+                    "h-usage",
+                    "Usage",
+                    vec![entry(2, "h-intro-1", "Intro", vec![])]
+                ),
+            ]
+        );
+        // the headings named after the ids of the layout don't clash with them.
+        assert!(md_to_html(&config, &images, "# Content").contains(r#"id="h-content""#));
+        // too few headings.
+        assert!(render_article(&config, &images, "## Intro").toc.is_empty());
+
+        // the marker is replaced in place without the automatic one.
+        let md = format!("[toc]\n\n{}", md);
+        let rendered = render_article(&config, &images, &md);
+        assert!(rendered.toc.is_empty());
+        assert!(!rendered.excerpt.text.contains("[toc]"));
+        let html = md_to_html(&config, &images, &md);
+        assert_eq!(rendered.html, html);
+        assert!(html.starts_with(r##"<nav class="toc"><ul><li><a href="#h-intro">Intro</a><ul><li><a href="#h-setup--run">Setup &amp; run</a></li></ul></li>"##));
+    }
+
+    #[test]
+    fn test_render_math() {
+        let html = r#"<p><span data-math-style="inline">a&lt;b</span></p>"#;
+        assert_eq!(render_math(MathRenderer::None, html), html);
+        assert_eq!(
+            render_math(MathRenderer::KaTeX, html),
+            r#"<p><span class="math math-inline">\(a&lt;b\)</span></p>"#
+        );
+        let mathml = render_math(MathRenderer::MathML, html);
+        assert!(mathml.starts_with("<p><math"));
+        assert!(mathml.contains("<mo>&lt;</mo>"));
+        let mathml = render_math(
+            MathRenderer::MathML,
+            r#"<span data-math-style="display">\frac{1}{2}</span>"#,
+        );
+        assert!(mathml.starts_with(r#"<math display="block">"#));
+        assert!(mathml.contains("<mfrac>"));
+        // no raw HTML gets through the math.
+        let mathml = latex_to_mathml(r#"\operatorname{<img src=x onerror="alert(1)">}"#, false);
+        assert!(!mathml.contains("<img"));
+        let mathml = latex_to_mathml(r"\unknown{<script>}", false);
+        assert!(mathml.contains("<merror") && !mathml.contains("<script>"));
+    }
+}
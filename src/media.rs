@@ -0,0 +1,367 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use object_store::{
+    aws::AmazonS3Builder, local::LocalFileSystem, path::Path as ObjectPath, prefix::PrefixStore,
+    ObjectStore,
+};
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    app::AppState,
+    config::{Config, MediaStorage},
+    images::{image_dimensions, resize_image},
+    models::MediaFile,
+    utils::Path as UrlPath,
+    Error,
+};
+
+pub const MEDIA_URL_PREFIX: &str = "/media";
+
+// Build the storage of the media files according to the config.
+pub fn build_store(config: &Config) -> Result<Arc<dyn ObjectStore>, Error> {
+    match config.media_storage() {
+        MediaStorage::Local => {
+            let media_dir = config.media_dir();
+            // don't fail the startup if the directory is not writable, the uploads fail instead.
+            if let Err(err) = std::fs::create_dir_all(&media_dir) {
+                warn!("failed creating media directory {}: {}", media_dir, err);
+                let media_dir = std::path::absolute(&media_dir)?;
+                return Ok(Arc::new(PrefixStore::new(
+                    LocalFileSystem::new(),
+                    ObjectPath::from_absolute_path(media_dir).map_err(object_store::Error::from)?,
+                )));
+            }
+            Ok(Arc::new(LocalFileSystem::new_with_prefix(media_dir)?))
+        }
+        MediaStorage::S3 => {
+            let s3 = config.media_s3().ok_or_else(|| {
+                Error::ConfigValidation("invalid media config, `[media.s3]` is missing".to_string())
+            })?;
+            // the credentials not in the config are read from the `AWS_*` environment variables.
+            let mut builder = AmazonS3Builder::from_env().with_bucket_name(&s3.bucket);
+            if let Some(endpoint) = &s3.endpoint {
+                // the local stand-ins like MinIO are usually served over plain HTTP with path-style URLs.
+                builder = builder
+                    .with_endpoint(endpoint)
+                    .with_allow_http(endpoint.starts_with("http://"))
+                    .with_virtual_hosted_style_request(false);
+            }
+            if let Some(region) = &s3.region {
+                builder = builder.with_region(region);
+            }
+            if let Some(access_key_id) = &s3.access_key_id {
+                builder = builder.with_access_key_id(access_key_id);
+            }
+            if let Some(secret_access_key) = &s3.secret_access_key {
+                builder = builder.with_secret_access_key(secret_access_key);
+            }
+            Ok(Arc::new(builder.build()?))
+        }
+    }
+}
+
+// Sanitize the uploaded file name to keep it safe to be used as both a file name and a URL path.
+fn sanitize_file_name(name: &str) -> String {
+    let name = Path::new(name)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_matches(|c| c == '.' || c == '-');
+    if name.is_empty() {
+        "untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// Check the size of the media file and sniff its MIME type from the content,
+// the file name and the type claimed by the client are never trusted.
+fn check_media(
+    bytes: &[u8],
+    max_size: usize,
+    allowed_types: &[String],
+) -> Result<&'static str, Error> {
+    if bytes.len() > max_size {
+        return Err(Error::MediaTooLarge(max_size));
+    }
+    let mime_type = sniff_mime_type(bytes);
+    if !allowed_types.iter().any(|allowed| allowed == mime_type) {
+        return Err(Error::MediaTypeNotAllowed(mime_type.to_string()));
+    }
+    Ok(mime_type)
+}
+
+// Save a media file into the media storage and record it in the media library.
+pub async fn save_media(state: &AppState, name: &str, bytes: &[u8]) -> Result<MediaFile, Error> {
+    let mime_type = check_media(
+        bytes,
+        state.config.media_max_size(),
+        state.config.media_allowed_types(),
+    )?;
+    // prefix the file name with the current time and a random suffix to avoid overwriting the existing ones.
+    let path = format!(
+        "{}-{:04x}-{}",
+        Utc::now().format("%Y%m%d%H%M%S"),
+        thread_rng().gen::<u16>(),
+        sanitize_file_name(name)
+    );
+    state
+        .media_store
+        .put(&ObjectPath::from(path.as_str()), bytes.to_vec().into())
+        .await?;
+    info!("saved media file {} ({} bytes)", path, bytes.len());
+
+    let dimensions = if mime_type.starts_with("image/") {
+        image_dimensions(bytes)
+    } else {
+        None
+    };
+    let media_file = MediaFile::insert(
+        &state.db,
+        &path,
+        name,
+        mime_type,
+        bytes.len() as i64,
+        dimensions,
+    )
+    .await?;
+    state.images.insert(&media_file);
+    Ok(media_file)
+}
+
+// Delete a media file from both the media storage and the media library.
+pub async fn delete_media(state: &AppState, media_file: &MediaFile) -> Result<(), Error> {
+    match state
+        .media_store
+        .delete(&ObjectPath::from(media_file.path.as_str()))
+        .await
+    {
+        // the file may have been removed from the storage manually.
+        Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+        Err(err) => return Err(err.into()),
+    }
+    // the cached variants are left to be cleared along with the cache directory.
+    state.images.remove(&media_file.path);
+    info!("deleted media file {}", media_file.path);
+    media_file.delete(&state.db).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaQuery {
+    // the width of the resized variant, one of the configured image widths.
+    w: Option<u32>,
+    // the format of the converted variant, only `webp` is supported.
+    format: Option<String>,
+}
+
+// Serve the media files from the media storage, the resized or converted variants of the images
+// are generated on the first request and cached on the local disk.
+pub async fn handler_media(
+    State(state): State<Arc<AppState>>,
+    UrlPath(path): UrlPath<String>,
+    Query(media_query): Query<MediaQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    // reject the paths escaping the storage, e.g. the ones with `..` segments.
+    let object_path = ObjectPath::parse(&path).map_err(|_| Error::NotFound)?;
+    if media_query.w.is_some() || media_query.format.is_some() {
+        return handler_media_variant(&state, &path, &object_path, media_query, &headers).await;
+    }
+    let result = match state.media_store.get(&object_path).await {
+        Ok(result) => result,
+        Err(object_store::Error::NotFound { .. }) => return Err(Error::NotFound),
+        Err(err) => return Err(err.into()),
+    };
+    let etag = result
+        .meta
+        .e_tag
+        .clone()
+        .map(|etag| format!("\"{}\"", etag));
+    if let Some(etag) = &etag {
+        if is_not_modified(&headers, etag) {
+            return Ok(StatusCode::NOT_MODIFIED.into_response());
+        }
+    }
+    let bytes = result.bytes().await?;
+    // the files uploaded before the media library have no records, so sniff their types.
+    let mime_type = match MediaFile::get_by_path(&state.db, &path).await {
+        Some(media_file) => media_file.mime_type,
+        None => sniff_mime_type(&bytes).to_string(),
+    };
+
+    Ok(media_response(&state, bytes.to_vec(), &mime_type, etag))
+}
+
+async fn handler_media_variant(
+    state: &AppState,
+    path: &str,
+    object_path: &ObjectPath,
+    media_query: MediaQuery,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
+    // only the configured variants of the resizable images are generated to bound the cache.
+    let info = state
+        .images
+        .get(path)
+        .filter(|info| info.is_resizable())
+        .ok_or(Error::NotFound)?;
+    let width = match media_query.w {
+        Some(width) if state.config.media_image_widths().contains(&width) => width,
+        Some(_) => return Err(Error::NotFound),
+        None => info.width,
+    };
+    let webp = match media_query.format.as_deref() {
+        Some("webp") if state.config.media_webp_enabled() => true,
+        Some(_) => return Err(Error::NotFound),
+        None => false,
+    };
+
+    let cache_path = PathBuf::from(state.config.media_cache_dir())
+        .join(width.to_string())
+        .join(if webp {
+            format!("{}.webp", path)
+        } else {
+            path.to_string()
+        });
+    let bytes = match tokio::fs::read(&cache_path).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let original = match state.media_store.get(object_path).await {
+                Ok(result) => result.bytes().await?,
+                Err(object_store::Error::NotFound { .. }) => return Err(Error::NotFound),
+                Err(err) => return Err(err.into()),
+            };
+            let bytes =
+                tokio::task::spawn_blocking(move || resize_image(&original, width, webp)).await??;
+            // serve the variant even if it can't be cached, e.g. the cache dir is not writable.
+            match write_cache(&cache_path, &bytes).await {
+                Ok(()) => info!("generated media variant {}", cache_path.display()),
+                Err(err) => warn!("failed caching {}: {}", cache_path.display(), err),
+            }
+            bytes
+        }
+        Err(err) => return Err(err.into()),
+    };
+    // the variants never change until the original is replaced, which gets a new path.
+    let etag = format!("\"{}-{}-{}\"", width, webp, bytes.len());
+    if is_not_modified(headers, &etag) {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+    let mime_type = sniff_mime_type(&bytes);
+    Ok(media_response(state, bytes, mime_type, Some(etag)))
+}
+
+// Write the cache file via a temporary file renamed into place, so that the concurrent readers
+// never see a partially written one.
+pub async fn write_cache(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let temp_path = path.with_extension(format!("{:08x}.tmp", thread_rng().gen::<u32>()));
+    tokio::fs::write(&temp_path, bytes).await?;
+    if let Err(err) = tokio::fs::rename(&temp_path, path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(err);
+    }
+    Ok(())
+}
+
+pub fn is_not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes() == etag.as_bytes())
+}
+
+fn sniff_mime_type(bytes: &[u8]) -> &'static str {
+    infer::get(bytes)
+        .map(|kind| kind.mime_type())
+        .unwrap_or("application/octet-stream")
+}
+
+pub fn media_response(
+    state: &AppState,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    etag: Option<String>,
+) -> Response {
+    let mut response = bytes.into_response();
+    let response_headers = response.headers_mut();
+    if let Ok(content_type) = HeaderValue::from_str(mime_type) {
+        response_headers.insert(CONTENT_TYPE, content_type);
+    }
+    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response_headers.insert(ETAG, etag);
+    }
+    response_headers.insert(CACHE_CONTROL, state.config.static_cache_control());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("photo.jpg"), "photo.jpg");
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_file_name("my photo (1).png"), "my-photo--1-.png");
+        assert_eq!(sanitize_file_name(".."), "untitled");
+        assert_eq!(sanitize_file_name(""), "untitled");
+    }
+
+    #[test]
+    fn test_check_media() {
+        let allowed_types = vec!["image/png".to_string()];
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(check_media(png, 1024, &allowed_types).unwrap(), "image/png");
+        assert!(matches!(
+            check_media(png, 8, &allowed_types),
+            Err(Error::MediaTooLarge(8))
+        ));
+        // a script named as an image is still rejected.
+        assert!(matches!(
+            check_media(b"<script>alert(1)</script>", 1024, &allowed_types),
+            Err(Error::MediaTypeNotAllowed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_write_cache() {
+        let dir = std::env::temp_dir().join(format!("rsomhap-cache-{}", std::process::id()));
+        let path = dir.join("480").join("photo.png");
+        write_cache(&path, b"variant").await.unwrap();
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"variant");
+        // no temporary file is left behind.
+        let mut entries = tokio::fs::read_dir(dir.join("480")).await.unwrap();
+        let mut count = 0;
+        while entries.next_entry().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 1);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
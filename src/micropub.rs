@@ -0,0 +1,741 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    extract::{RawQuery, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE, LOCATION},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Json, Response},
+};
+use chrono_tz::Tz;
+use serde_json::{json, Map, Value};
+use subtle::ConstantTimeEq;
+use tracing::{error, info};
+use url::{form_urlencoded, Url};
+
+use crate::{
+    app::AppState,
+    models::Article,
+    utils::{parse_local_datetime, Editable, EditorForm},
+};
+
+// The max length of the title derived from the content when an entry has no name.
+const DERIVED_TITLE_MAX_LENGTH: usize = 50;
+
+// The properties of an h-entry, each property holds a list of values as in the microformats2 JSON.
+type Properties = HashMap<String, Vec<Value>>;
+
+// An error response defined by the Micropub specification.
+#[derive(Debug)]
+struct MicropubError {
+    status: StatusCode,
+    error: &'static str,
+    description: String,
+}
+
+impl MicropubError {
+    fn unauthorized() -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            error: "unauthorized",
+            description: "no access token was provided".to_string(),
+        }
+    }
+
+    fn forbidden() -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            error: "forbidden",
+            description: "the access token is invalid".to_string(),
+        }
+    }
+
+    fn invalid_request(description: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "invalid_request",
+            description: description.into(),
+        }
+    }
+
+    fn server_error() -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "server_error",
+            description: "failed to save the changes, please try again".to_string(),
+        }
+    }
+}
+
+impl IntoResponse for MicropubError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(json!({
+                "error": self.error,
+                "error_description": self.description,
+            })),
+        )
+            .into_response()
+    }
+}
+
+// The actions a Micropub request can perform on an entry.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Create(Properties),
+    Update {
+        url: String,
+        replace: Properties,
+        add: Properties,
+        // properties to be removed entirely.
+        delete_properties: Vec<String>,
+        // values to be removed from the properties.
+        delete_values: Properties,
+    },
+    Delete {
+        url: String,
+    },
+}
+
+// Parse a form-encoded body or query string into a multimap, the `[]` suffix of array keys is stripped.
+fn parse_form(input: &[u8]) -> HashMap<String, Vec<String>> {
+    form_urlencoded::parse(input).fold(HashMap::new(), |mut acc, (key, value)| {
+        let key = key.strip_suffix("[]").unwrap_or(&key).to_string();
+        acc.entry(key).or_default().push(value.into_owned());
+        acc
+    })
+}
+
+fn first_value(form: &HashMap<String, Vec<String>>, key: &str) -> Option<String> {
+    form.get(key).and_then(|values| values.first()).cloned()
+}
+
+fn parse_form_action(form: HashMap<String, Vec<String>>) -> Result<Action, MicropubError> {
+    match first_value(&form, "action").as_deref() {
+        Some("delete") => Ok(Action::Delete {
+            url: first_value(&form, "url")
+                .ok_or_else(|| MicropubError::invalid_request("missing the url to delete"))?,
+        }),
+        Some(action) => Err(MicropubError::invalid_request(format!(
+            "action {} is not supported in form-encoded requests",
+            action
+        ))),
+        None => {
+            if first_value(&form, "h").as_deref() != Some("entry") {
+                return Err(MicropubError::invalid_request("only h-entry is supported"));
+            }
+            let properties = form
+                .into_iter()
+                // skip the reserved parameters and the server commands.
+                .filter(|(key, _)| {
+                    !matches!(key.as_str(), "h" | "access_token" | "action" | "url")
+                        && !key.starts_with("mp-")
+                })
+                .map(|(key, values)| (key, values.into_iter().map(Value::String).collect()))
+                .collect();
+            Ok(Action::Create(properties))
+        }
+    }
+}
+
+fn json_to_properties(value: Option<&Value>) -> Result<Properties, MicropubError> {
+    let object = match value {
+        Some(Value::Object(object)) => object,
+        None => return Ok(Properties::new()),
+        _ => {
+            return Err(MicropubError::invalid_request(
+                "properties must be an object",
+            ))
+        }
+    };
+    object
+        .iter()
+        .map(|(key, values)| match values {
+            Value::Array(values) => Ok((key.clone(), values.clone())),
+            _ => Err(MicropubError::invalid_request(format!(
+                "property {} must be an array",
+                key
+            ))),
+        })
+        .collect()
+}
+
+fn parse_json_action(body: &Value) -> Result<Action, MicropubError> {
+    let url = || {
+        body.get("url")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| MicropubError::invalid_request("missing the url of the entry"))
+    };
+    match body.get("action").and_then(Value::as_str) {
+        Some("delete") => Ok(Action::Delete { url: url()? }),
+        Some("update") => {
+            let (delete_properties, delete_values) = match body.get("delete") {
+                Some(Value::Array(names)) => (
+                    names
+                        .iter()
+                        .filter_map(|name| name.as_str().map(str::to_string))
+                        .collect(),
+                    Properties::new(),
+                ),
+                delete => (vec![], json_to_properties(delete)?),
+            };
+            Ok(Action::Update {
+                url: url()?,
+                replace: json_to_properties(body.get("replace"))?,
+                add: json_to_properties(body.get("add"))?,
+                delete_properties,
+                delete_values,
+            })
+        }
+        Some(action) => Err(MicropubError::invalid_request(format!(
+            "action {} is not supported",
+            action
+        ))),
+        None => {
+            let is_entry = body
+                .get("type")
+                .and_then(Value::as_array)
+                .and_then(|types| types.first())
+                .and_then(Value::as_str)
+                == Some("h-entry");
+            if !is_entry {
+                return Err(MicropubError::invalid_request("only h-entry is supported"));
+            }
+            Ok(Action::Create(json_to_properties(body.get("properties"))?))
+        }
+    }
+}
+
+// Get the plain text of a property value, which may be a string or an object like `{"value": ...}`.
+fn value_to_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Object(object) => object
+            .get("value")
+            .or_else(|| object.get("html"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+fn first_text(properties: &Properties, key: &str) -> Option<String> {
+    properties
+        .get(key)
+        .and_then(|values| values.first())
+        .and_then(value_to_text)
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+// Map the properties of an h-entry into the editor form, so it can go through the same paths as the editor.
+fn properties_to_form(
+    id: Option<i32>,
+    properties: &Properties,
+    timezone: Tz,
+) -> Result<EditorForm, MicropubError> {
+    let mut content = first_text(properties, "content").unwrap_or_default();
+    // append the photos to the content as Markdown images.
+    for photo in properties.get("photo").into_iter().flatten() {
+        let alt = photo.get("alt").and_then(Value::as_str).unwrap_or_default();
+        if let Some(url) = value_to_text(photo) {
+            content.push_str(&format!("\n\n![{}]({})", alt, url));
+        }
+    }
+    // notes have no name, so use the beginning of the content as the title.
+    let title = first_text(properties, "name").unwrap_or_else(|| {
+        let first_line = content.trim().lines().next().unwrap_or_default();
+        if first_line.chars().count() > DERIVED_TITLE_MAX_LENGTH {
+            format!(
+                "{}...",
+                first_line
+                    .chars()
+                    .take(DERIVED_TITLE_MAX_LENGTH)
+                    .collect::<String>()
+            )
+        } else {
+            first_line.to_string()
+        }
+    });
+    let tags = properties
+        .get("category")
+        .into_iter()
+        .flatten()
+        .filter_map(value_to_text)
+        .collect::<Vec<_>>()
+        .join(",");
+    // the properties of an update are the whole entry, so an absent one has been deleted
+    // and is cleared instead of kept.
+    let optional_text = |key| first_text(properties, key).or_else(|| id.map(|_| String::new()));
+    // normalize the publish date like the editor does, an invalid one is rejected instead of ignored.
+    let created_at = first_text(properties, "published")
+        .map(|published| {
+            parse_local_datetime(&published, timezone)
+                .map(|created_at| created_at.to_rfc3339())
+                .ok_or_else(|| {
+                    MicropubError::invalid_request(format!("invalid published date {}", published))
+                })
+        })
+        .transpose()?;
+
+    Ok(EditorForm {
+        id,
+        title: Some(title),
+        tags: Some(tags),
+        content: Some(content.trim().to_string()),
+        created_at,
+        keep_updated_at: None,
+        version: None,
+        cover_image: optional_text("featured"),
+        summary: optional_text("summary"),
+        hide_toc: None,
+    })
+}
+
+fn article_to_properties(article: &Article) -> Properties {
+    let mut properties = Properties::new();
+    properties.insert("name".to_string(), vec![json!(article.title)]);
+    properties.insert("content".to_string(), vec![json!(article.content)]);
+    properties.insert(
+        "category".to_string(),
+        article
+            .tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| json!(tag))
+            .collect(),
+    );
+    properties.insert(
+        "published".to_string(),
+        vec![json!(article.created_at.to_rfc3339())],
+    );
+    if let Some(cover_image) = article.cover_image.as_ref().filter(|s| !s.is_empty()) {
+        properties.insert("featured".to_string(), vec![json!(cover_image)]);
+    }
+    if let Some(summary) = article.summary.as_ref().filter(|s| !s.is_empty()) {
+        properties.insert("summary".to_string(), vec![json!(summary)]);
+    }
+    properties
+}
+
+fn apply_update(
+    properties: &mut Properties,
+    replace: Properties,
+    add: Properties,
+    delete_properties: Vec<String>,
+    delete_values: Properties,
+) {
+    for (key, values) in replace {
+        properties.insert(key, values);
+    }
+    for (key, values) in add {
+        properties.entry(key).or_default().extend(values);
+    }
+    for key in delete_properties {
+        properties.remove(&key);
+    }
+    for (key, values) in delete_values {
+        if let Some(existing) = properties.get_mut(&key) {
+            existing.retain(|value| !values.contains(value));
+        }
+    }
+}
+
+fn article_url(state: &AppState, id: i32) -> String {
+    format!(
+        "{}/article/{}",
+        state.config.blog_url().trim_end_matches('/'),
+        id
+    )
+}
+
+// Resolve the article ID from its URL, relative URLs are resolved against the blog URL and the
+// absolute ones must be under it.
+fn article_id_from_url(blog_url: &str, url: &str) -> Option<i32> {
+    let blog_url = Url::parse(blog_url).ok()?;
+    let url = blog_url.join(url).ok()?;
+    if url.origin() != blog_url.origin() {
+        return None;
+    }
+    let path = url
+        .path()
+        .strip_prefix(blog_url.path().trim_end_matches('/'))?;
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("article"), Some(id), None) => id.parse().ok(),
+        _ => None,
+    }
+}
+
+async fn get_article_by_url(state: &AppState, url: &str) -> Result<Article, MicropubError> {
+    let id = article_id_from_url(&state.config.blog_url(), url)
+        .ok_or_else(|| MicropubError::invalid_request(format!("{} is not an article", url)))?;
+    Article::get_by_id(&state.db, id)
+        .await
+        .ok_or_else(|| MicropubError::invalid_request(format!("article {} does not exist", id)))
+}
+
+// Check the access token from either the `Authorization` header or the `access_token` parameter.
+fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+    access_token: Option<String>,
+) -> Result<(), MicropubError> {
+    let header_token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    let token = match (header_token, access_token) {
+        (Some(_), Some(_)) => {
+            return Err(MicropubError::invalid_request(
+                "the access token must be provided in only one place",
+            ))
+        }
+        (Some(token), None) | (None, Some(token)) => token,
+        (None, None) => return Err(MicropubError::unauthorized()),
+    };
+    match state.config.micropub_token() {
+        // compare in constant time to not leak the token via the timing.
+        Some(expected)
+            if !expected.is_empty() && bool::from(expected.as_bytes().ct_eq(token.as_bytes())) =>
+        {
+            Ok(())
+        }
+        _ => Err(MicropubError::forbidden()),
+    }
+}
+
+pub async fn handler_micropub_get(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+) -> Response {
+    let query = parse_form(query.unwrap_or_default().as_bytes());
+    if let Err(err) = authorize(&state, &headers, first_value(&query, "access_token")) {
+        return err.into_response();
+    }
+
+    match first_value(&query, "q").as_deref() {
+        Some("config") => Json(json!({"syndicate-to": []})).into_response(),
+        Some("syndicate-to") => Json(json!({"syndicate-to": []})).into_response(),
+        Some("source") => {
+            let url = match first_value(&query, "url") {
+                Some(url) => url,
+                None => return MicropubError::invalid_request("missing the url").into_response(),
+            };
+            let article = match get_article_by_url(&state, &url).await {
+                Ok(article) => article,
+                Err(err) => return err.into_response(),
+            };
+            let properties = article_to_properties(&article);
+            // only return the requested properties if specified.
+            match query.get("properties") {
+                Some(keys) => Json(json!({
+                    "properties": keys
+                        .iter()
+                        .filter_map(|key| properties.get(key).map(|values| (key.clone(), json!(values))))
+                        .collect::<Map<_, _>>(),
+                })),
+                None => Json(json!({
+                    "type": ["h-entry"],
+                    "properties": properties,
+                })),
+            }
+            .into_response()
+        }
+        _ => MicropubError::invalid_request("unsupported query").into_response(),
+    }
+}
+
+pub async fn handler_micropub_post(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    // parse the request body according to its content type.
+    let (action, access_token) = if is_json {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(body) => (parse_json_action(&body), None),
+            Err(err) => {
+                return MicropubError::invalid_request(format!("invalid JSON body: {}", err))
+                    .into_response()
+            }
+        }
+    } else {
+        let form = parse_form(&body);
+        let access_token = first_value(&form, "access_token");
+        (parse_form_action(form), access_token)
+    };
+    if let Err(err) = authorize(&state, &headers, access_token) {
+        return err.into_response();
+    }
+    let action = match action {
+        Ok(action) => action,
+        Err(err) => return err.into_response(),
+    };
+
+    match action {
+        Action::Create(properties) => {
+            let article = match properties_to_form(None, &properties, state.config.timezone()) {
+                Ok(form) => Article::from(form),
+                Err(err) => return err.into_response(),
+            };
+            info!("inserting {} via Micropub", article);
+            match article.insert(&state.db).await {
+                Ok(Article { id: Some(id), .. }) => {
+                    (StatusCode::CREATED, [(LOCATION, article_url(&state, id))]).into_response()
+                }
+                Ok(_) => MicropubError::server_error().into_response(),
+                Err(err) => {
+                    error!("failed inserting {} via Micropub: {:?}", article, err);
+                    MicropubError::server_error().into_response()
+                }
+            }
+        }
+        Action::Update {
+            url,
+            replace,
+            add,
+            delete_properties,
+            delete_values,
+        } => {
+            let current = match get_article_by_url(&state, &url).await {
+                Ok(article) => article,
+                Err(err) => return err.into_response(),
+            };
+            let mut properties = article_to_properties(&current);
+            apply_update(
+                &mut properties,
+                replace,
+                add,
+                delete_properties,
+                delete_values,
+            );
+            let article = match properties_to_form(current.id, &properties, state.config.timezone())
+            {
+                Ok(form) => Article::from(form),
+                Err(err) => return err.into_response(),
+            };
+            info!("updating {} via Micropub", article);
+            match article.update(&state.db).await {
+                Ok(_) => StatusCode::NO_CONTENT.into_response(),
+                Err(err) => {
+                    error!("failed updating {} via Micropub: {:?}", article, err);
+                    MicropubError::server_error().into_response()
+                }
+            }
+        }
+        Action::Delete { url } => {
+            let article = match get_article_by_url(&state, &url).await {
+                Ok(article) => article,
+                Err(err) => return err.into_response(),
+            };
+            info!("deleting {} via Micropub", article);
+            match article.delete(&state.db).await {
+                Ok(()) => StatusCode::NO_CONTENT.into_response(),
+                Err(err) => {
+                    error!("failed deleting {} via Micropub: {:?}", article, err);
+                    MicropubError::server_error().into_response()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_form_action() {
+        let form = parse_form(b"h=entry&name=Hello&content=World&category[]=foo&category[]=bar&mp-slug=hi&access_token=t");
+        let properties = match parse_form_action(form).unwrap() {
+            Action::Create(properties) => properties,
+            action => panic!("unexpected action {:?}", action),
+        };
+        let form = properties_to_form(None, &properties, Tz::UTC).unwrap();
+        assert_eq!(form.title.as_deref(), Some("Hello"));
+        assert_eq!(form.content.as_deref(), Some("World"));
+        assert_eq!(form.tags.as_deref(), Some("foo,bar"));
+        assert!(!properties.contains_key("mp-slug"));
+        assert!(!properties.contains_key("access_token"));
+
+        assert_eq!(
+            parse_form_action(parse_form(b"action=delete&url=/article/1")).unwrap(),
+            Action::Delete {
+                url: "/article/1".to_string()
+            }
+        );
+        assert!(parse_form_action(parse_form(b"h=card&name=Hello")).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_action() {
+        let body = json!({
+            "type": ["h-entry"],
+            "properties": {
+                "content": [{"html": "<p>Hello</p>", "value": "Hello"}],
+                "photo": [{"value": "https://example.com/a.jpg", "alt": "A"}],
+            },
+        });
+        let properties = match parse_json_action(&body).unwrap() {
+            Action::Create(properties) => properties,
+            action => panic!("unexpected action {:?}", action),
+        };
+        let form = properties_to_form(None, &properties, Tz::UTC).unwrap();
+        // the title is derived from the content when the entry has no name.
+        assert_eq!(form.title.as_deref(), Some("Hello"));
+        assert_eq!(
+            form.content.as_deref(),
+            Some("Hello\n\n![A](https://example.com/a.jpg)")
+        );
+
+        let body = json!({
+            "action": "update",
+            "url": "https://example.com/article/1",
+            "replace": {"content": ["New"]},
+            "add": {"category": ["baz"]},
+            "delete": {"category": ["foo"]},
+        });
+        let mut properties = Properties::from([
+            ("content".to_string(), vec![json!("Old")]),
+            ("category".to_string(), vec![json!("foo"), json!("bar")]),
+        ]);
+        match parse_json_action(&body).unwrap() {
+            Action::Update {
+                replace,
+                add,
+                delete_properties,
+                delete_values,
+                ..
+            } => apply_update(
+                &mut properties,
+                replace,
+                add,
+                delete_properties,
+                delete_values,
+            ),
+            action => panic!("unexpected action {:?}", action),
+        }
+        assert_eq!(properties["content"], vec![json!("New")]);
+        assert_eq!(properties["category"], vec![json!("bar"), json!("baz")]);
+    }
+
+    #[test]
+    fn test_published() {
+        let properties = Properties::from([
+            ("content".to_string(), vec![json!("Hello")]),
+            (
+                "published".to_string(),
+                vec![json!("2024-08-22T09:30:00+08:00")],
+            ),
+        ]);
+        let form = properties_to_form(None, &properties, Tz::UTC).unwrap();
+        assert_eq!(
+            form.created_at.as_deref(),
+            Some("2024-08-22T01:30:00+00:00")
+        );
+        // the one without the offset is in the configured timezone like the editor.
+        let properties =
+            Properties::from([("published".to_string(), vec![json!("2024-08-22 09:30")])]);
+        let form = properties_to_form(None, &properties, Tz::Asia__Shanghai).unwrap();
+        assert_eq!(
+            form.created_at.as_deref(),
+            Some("2024-08-22T01:30:00+00:00")
+        );
+        let properties = Properties::from([("published".to_string(), vec![json!("yesterday")])]);
+        let err = match properties_to_form(None, &properties, Tz::UTC) {
+            Err(err) => err,
+            Ok(_) => panic!("the invalid published date is accepted"),
+        };
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.error, "invalid_request");
+    }
+
+    #[test]
+    fn test_update_optional_properties() {
+        let article = Article {
+            id: Some(1),
+            title: "Hello".to_string(),
+            content: "World".to_string(),
+            cover_image: Some("/media/a.png".to_string()),
+            summary: Some("Hi".to_string()),
+            ..Default::default()
+        };
+        let mut properties = article_to_properties(&article);
+        assert_eq!(properties["featured"], vec![json!("/media/a.png")]);
+        assert_eq!(properties["summary"], vec![json!("Hi")]);
+        // the untouched properties are kept.
+        let form = properties_to_form(article.id, &properties, Tz::UTC).unwrap();
+        assert_eq!(form.cover_image.as_deref(), Some("/media/a.png"));
+        assert_eq!(form.summary.as_deref(), Some("Hi"));
+
+        let body = json!({
+            "action": "update",
+            "url": "https://example.com/article/1",
+            "delete": ["summary", "featured"],
+        });
+        match parse_json_action(&body).unwrap() {
+            Action::Update {
+                replace,
+                add,
+                delete_properties,
+                delete_values,
+                ..
+            } => apply_update(
+                &mut properties,
+                replace,
+                add,
+                delete_properties,
+                delete_values,
+            ),
+            action => panic!("unexpected action {:?}", action),
+        }
+        // the deleted properties are cleared.
+        let form = properties_to_form(article.id, &properties, Tz::UTC).unwrap();
+        assert_eq!(form.cover_image.as_deref(), Some(""));
+        assert_eq!(form.summary.as_deref(), Some(""));
+        // while a new entry just has none.
+        let form = properties_to_form(None, &properties, Tz::UTC).unwrap();
+        assert_eq!(form.cover_image, None);
+        assert_eq!(form.summary, None);
+    }
+
+    #[test]
+    fn test_article_id_from_url() {
+        let blog_url = "https://example.com";
+        assert_eq!(
+            article_id_from_url(blog_url, "https://example.com/article/42"),
+            Some(42)
+        );
+        assert_eq!(article_id_from_url(blog_url, "/article/42/"), Some(42));
+        assert_eq!(article_id_from_url(blog_url, "/tag/42"), None);
+        assert_eq!(article_id_from_url(blog_url, "/article/foo"), None);
+        // the articles of the other sites.
+        assert_eq!(
+            article_id_from_url(blog_url, "https://other.host/article/42"),
+            None
+        );
+        assert_eq!(
+            article_id_from_url(blog_url, "http://example.com/article/42"),
+            None
+        );
+        // the blog under a path.
+        let blog_url = "https://example.com/blog/";
+        assert_eq!(
+            article_id_from_url(blog_url, "https://example.com/blog/article/42"),
+            Some(42)
+        );
+        assert_eq!(article_id_from_url(blog_url, "/article/42"), None);
+    }
+}
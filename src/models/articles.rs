@@ -1,36 +1,82 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    sync::LazyLock,
+};
 
 use axum::async_trait;
 use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use tracing::info;
 
 use crate::{
+    markdown::Excerpt,
+    models::Revision,
     utils::{sort_out_tags, Editable, EditorForm},
     Error,
 };
 
+// The URL of the first Markdown image, without the optional title.
+static FIRST_IMAGE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[[^\]]*\]\(\s*<?([^\s)>]+)").unwrap());
+
 #[derive(FromRow, Serialize, Deserialize, Default)]
 pub struct Article {
-    id: Option<i32>,
-    title: String,
+    pub id: Option<i32>,
+    pub title: String,
     pub content: String,
     pub tags: String,
     pub created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // increased on every update to detect the concurrent edits.
+    pub version: i32,
+    // the image of the social cards, falls back to the first image in the content if unset.
+    pub cover_image: Option<String>,
+    // the description of the social cards, falls back to the beginning of the content if unset.
+    pub summary: Option<String>,
+    // hide the automatic table of contents, the `[toc]` marker in the content still works.
+    pub hide_toc: bool,
+    // the version the editor was based on, `None` means updating unconditionally.
+    #[sqlx(skip)]
+    #[serde(skip)]
+    pub base_version: Option<i32>,
+    // the publish date requested by the editor, `None` means now for a new article or unchanged.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub new_created_at: Option<DateTime<Utc>>,
+    // the `hide_toc` requested by the editor, `None` means false for a new article or unchanged.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub new_hide_toc: Option<bool>,
+    // keep `updated_at` unchanged when saving, e.g. for the minor edits.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub keep_updated_at: bool,
+    // the rendered HTML of the excerpt, filled by `AppState::fill_excerpts` before rendering.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub excerpt: String,
+    // the plain text of the excerpt, e.g. for the meta descriptions.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub excerpt_text: String,
+    // whether the article continues after the excerpt.
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub has_more: bool,
 }
 
 impl Article {
     pub async fn get_all(db: &sqlx::MySqlPool) -> Vec<Self> {
-        sqlx::query_as("SELECT * FROM articles ORDER BY id DESC")
+        sqlx::query_as("SELECT * FROM articles ORDER BY created_at DESC, id DESC")
             .fetch_all(db)
             .await
             .unwrap_or_default()
     }
 
     pub async fn get_on_page(db: &sqlx::MySqlPool, page: u32, article_per_page: u32) -> Vec<Self> {
-        sqlx::query_as("SELECT * FROM articles ORDER BY id DESC LIMIT ? OFFSET ?")
+        sqlx::query_as("SELECT * FROM articles ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?")
             .bind(article_per_page)
             .bind((page - 1) * article_per_page)
             .fetch_all(db)
@@ -55,11 +101,11 @@ impl Article {
 
     pub async fn get_by_tag(db: &sqlx::MySqlPool, tag: &str) -> Vec<Self> {
         sqlx::query_as(
-            "SELECT a.id, a.title, a.content, a.tags, a.created_at, a.updated_at
+            "SELECT a.id, a.title, a.content, a.tags, a.created_at, a.updated_at, a.version, a.cover_image, a.summary, a.hide_toc
              FROM articles AS a
              INNER JOIN tags AS t ON a.id = t.article_id
              WHERE t.name = ?
-             ORDER BY a.id DESC",
+             ORDER BY a.created_at DESC, a.id DESC",
         )
         .bind(tag)
         .fetch_all(db)
@@ -74,6 +120,34 @@ impl Article {
             .ok()
     }
 
+    // get the count, the sum of the versions and the latest update in one query.
+    pub async fn get_revision(db: &sqlx::MySqlPool) -> Revision {
+        sqlx::query_as(
+            "SELECT COUNT(*) AS count, CAST(COALESCE(SUM(version), 0) AS SIGNED) AS total_version, MAX(updated_at) AS latest_updated FROM articles",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap_or_default()
+    }
+
+    pub fn set_excerpt(&mut self, excerpt: Excerpt) {
+        self.excerpt = excerpt.html;
+        self.excerpt_text = excerpt.text;
+        self.has_more = excerpt.more;
+    }
+
+    // get the image of the social cards, the cover image or the first image in the content.
+    pub fn social_image(&self) -> Option<String> {
+        self.cover_image
+            .clone()
+            .filter(|cover_image| !cover_image.is_empty())
+            .or_else(|| {
+                FIRST_IMAGE_RE
+                    .captures(&self.content)
+                    .map(|caps| caps[1].to_string())
+            })
+    }
+
     async fn clear_tags(&self, tx: &mut sqlx::Transaction<'_, sqlx::MySql>) -> Result<(), Error> {
         sqlx::query("DELETE FROM tags WHERE article_id = ?")
             .bind(self.id)
@@ -102,6 +176,20 @@ impl Display for Article {
 
 #[async_trait]
 impl Editable for Article {
+    const KIND: &'static str = "article";
+
+    fn id(&self) -> Option<i32> {
+        self.id
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    async fn get_latest(db: &sqlx::MySqlPool, id: i32) -> Option<Self> {
+        Self::get_by_id(db, id).await
+    }
+
     fn get_redirect_url(&self) -> String {
         match self.id {
             Some(id) => format!("/article/{}", id),
@@ -117,16 +205,33 @@ impl Editable for Article {
 
         let mut tx = db.begin().await?;
 
-        // update the articles table
-        sqlx::query(
-            "UPDATE articles SET title = ?, content = ?, tags = ?, updated_at = NOW() WHERE id = ?",
+        // update the articles table, setting `updated_at` to itself explicitly also prevents
+        // the `ON UPDATE CURRENT_TIMESTAMP` from taking effect.
+        let result = sqlx::query(
+            "UPDATE articles SET title = ?, content = ?, tags = ?, cover_image = IF(? IS NULL, cover_image, NULLIF(?, '')), summary = IF(? IS NULL, summary, NULLIF(?, '')), hide_toc = COALESCE(?, hide_toc), created_at = COALESCE(?, created_at), updated_at = IF(?, updated_at, NOW()), version = version + 1 WHERE id = ? AND (? IS NULL OR version = ?)",
         )
         .bind(&self.title)
         .bind(&self.content)
         .bind(&self.tags)
+        .bind(&self.cover_image)
+        .bind(&self.cover_image)
+        .bind(&self.summary)
+        .bind(&self.summary)
+        .bind(self.new_hide_toc)
+        .bind(self.new_created_at)
+        .bind(self.keep_updated_at)
         .bind(id)
+        .bind(self.base_version)
+        .bind(self.base_version)
         .execute(&mut *tx)
         .await?;
+        // nothing is updated if the article has been modified since the editor loaded it.
+        if result.rows_affected() == 0 {
+            return match Self::get_by_id(db, id).await {
+                Some(_) => Err(Error::EditConflict(id)),
+                None => Err(sqlx::Error::RowNotFound.into()),
+            };
+        }
         info!("updated article {} with id {}", self.title, id);
         // update the tags table
         self.clear_tags(&mut tx).await?;
@@ -136,19 +241,26 @@ impl Editable for Article {
 
         tx.commit().await?;
 
-        Ok(Self::get_by_id(db, id).await.unwrap())
+        Self::get_by_id(db, id)
+            .await
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
     }
 
     async fn insert(&self, db: &sqlx::MySqlPool) -> Result<Self, Error> {
         let mut tx = db.begin().await?;
 
-        // insert into the articles table
+        // insert into the articles table, a backdated article is also updated at its publish date.
         sqlx::query(
-            "INSERT INTO articles (title, content, tags, created_at, updated_at) VALUES (?, ?, ?, NOW(), NOW())",
+            "INSERT INTO articles (title, content, tags, cover_image, summary, hide_toc, created_at, updated_at) VALUES (?, ?, ?, NULLIF(?, ''), NULLIF(?, ''), COALESCE(?, FALSE), COALESCE(?, NOW()), COALESCE(?, NOW()))",
         )
         .bind(&self.title)
         .bind(&self.content)
         .bind(&self.tags)
+        .bind(&self.cover_image)
+        .bind(&self.summary)
+        .bind(self.new_hide_toc)
+        .bind(self.new_created_at)
+        .bind(self.new_created_at)
         .execute(&mut *tx)
         .await?;
         // get the last inserted id
@@ -162,7 +274,9 @@ impl Editable for Article {
 
         tx.commit().await?;
 
-        Ok(Self::get_by_id(db, id).await.unwrap())
+        Self::get_by_id(db, id)
+            .await
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
     }
 
     async fn delete(&self, db: &sqlx::MySqlPool) -> Result<(), Error> {
@@ -195,6 +309,21 @@ impl From<EditorForm> for Article {
             title: from.title.unwrap_or_default().trim().to_string(),
             tags: sort_out_tags(&from.tags.unwrap_or_default()),
             content: from.content.unwrap_or_default(),
+            // the publish date is already validated and normalized into RFC 3339 format by the
+            // editor, Micropub and XML-RPC request layers.
+            new_created_at: from
+                .created_at
+                .and_then(|created_at| DateTime::parse_from_rfc3339(&created_at).ok())
+                .map(|created_at| created_at.with_timezone(&Utc)),
+            // `None` keeps the existing ones, e.g. from the clients without these fields,
+            // while an empty one clears them.
+            cover_image: from
+                .cover_image
+                .map(|cover_image| cover_image.trim().to_string()),
+            summary: from.summary.map(|summary| summary.trim().to_string()),
+            new_hide_toc: from.hide_toc,
+            keep_updated_at: from.keep_updated_at.is_some(),
+            base_version: from.version,
             ..Default::default()
         }
     }
@@ -202,7 +331,7 @@ impl From<EditorForm> for Article {
 
 #[derive(FromRow, Serialize)]
 pub struct Tags {
-    name: String,
+    pub name: String,
     num: i32,
 }
 
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+
+use crate::{utils::Editable, Error};
+
+// The unsubmitted editor content saved periodically, one per user and entity.
+#[derive(Clone, Debug, FromRow, Serialize)]
+pub struct Autosave {
+    pub title: String,
+    pub tags: String,
+    pub content: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+impl Autosave {
+    // whether the autosave is worth restoring, the ones older than the saved entity are stale since
+    // they were submitted or overwritten afterwards.
+    pub fn is_newer_than<T: Editable>(&self, entity: Option<&T>) -> bool {
+        entity.is_none_or(|entity| self.saved_at > entity.updated_at())
+    }
+
+    // `id` is `None` for the new article or page which isn't inserted yet.
+    pub async fn get(
+        db: &sqlx::MySqlPool,
+        username: &str,
+        kind: &str,
+        id: Option<i32>,
+    ) -> Option<Self> {
+        sqlx::query_as(
+            "SELECT title, tags, content, saved_at FROM autosaves WHERE username = ? AND entity_type = ? AND entity_id = ?",
+        )
+        .bind(username)
+        .bind(kind)
+        .bind(id.unwrap_or(0))
+        .fetch_one(db)
+        .await
+        .ok()
+    }
+
+    pub async fn save(
+        db: &sqlx::MySqlPool,
+        username: &str,
+        kind: &str,
+        id: Option<i32>,
+        title: &str,
+        tags: &str,
+        content: &str,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO autosaves (username, entity_type, entity_id, title, tags, content, saved_at) VALUES (?, ?, ?, ?, ?, ?, NOW()) ON DUPLICATE KEY UPDATE title = VALUES(title), tags = VALUES(tags), content = VALUES(content), saved_at = NOW()",
+        )
+        .bind(username)
+        .bind(kind)
+        .bind(id.unwrap_or(0))
+        .bind(title)
+        .bind(tags)
+        .bind(content)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(
+        db: &sqlx::MySqlPool,
+        username: &str,
+        kind: &str,
+        id: Option<i32>,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "DELETE FROM autosaves WHERE username = ? AND entity_type = ? AND entity_id = ?",
+        )
+        .bind(username)
+        .bind(kind)
+        .bind(id.unwrap_or(0))
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Article;
+
+    #[test]
+    fn test_is_newer_than() {
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+        let autosave = Autosave {
+            title: "Draft".to_string(),
+            tags: String::new(),
+            content: String::new(),
+            saved_at: at("2024-08-22T01:00:00Z"),
+        };
+        let article = |updated_at| Article {
+            id: Some(1),
+            updated_at,
+            ..Default::default()
+        };
+        // the new article isn't saved at all.
+        assert!(autosave.is_newer_than::<Article>(None));
+        assert!(autosave.is_newer_than(Some(&article(at("2024-08-22T00:59:59Z")))));
+        assert!(!autosave.is_newer_than(Some(&article(at("2024-08-22T01:00:00Z")))));
+        assert!(!autosave.is_newer_than(Some(&article(at("2024-08-22T01:00:01Z")))));
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+
+use crate::{media::MEDIA_URL_PREFIX, Error};
+
+// An uploaded media file, the content lives in the media storage under `path`.
+#[derive(Clone, Debug, FromRow, Serialize)]
+pub struct MediaFile {
+    pub id: i32,
+    pub path: String,
+    // the original file name when uploaded.
+    pub name: String,
+    pub mime_type: String,
+    pub size: i64,
+    // the dimensions of the images, `None` for the other files.
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MediaFile {
+    pub async fn get_all(db: &sqlx::MySqlPool) -> Vec<Self> {
+        sqlx::query_as("SELECT * FROM media_files ORDER BY created_at DESC, id DESC")
+            .fetch_all(db)
+            .await
+            .unwrap_or_default()
+    }
+
+    pub async fn get_by_id(db: &sqlx::MySqlPool, id: i32) -> Option<Self> {
+        sqlx::query_as("SELECT * FROM media_files WHERE id = ?")
+            .bind(id)
+            .fetch_one(db)
+            .await
+            .ok()
+    }
+
+    pub async fn get_by_path(db: &sqlx::MySqlPool, path: &str) -> Option<Self> {
+        sqlx::query_as("SELECT * FROM media_files WHERE path = ?")
+            .bind(path)
+            .fetch_one(db)
+            .await
+            .ok()
+    }
+
+    pub async fn insert(
+        db: &sqlx::MySqlPool,
+        path: &str,
+        name: &str,
+        mime_type: &str,
+        size: i64,
+        dimensions: Option<(u32, u32)>,
+    ) -> Result<Self, Error> {
+        let id = sqlx::query(
+            "INSERT INTO media_files (path, name, mime_type, size, width, height) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(path)
+        .bind(name)
+        .bind(mime_type)
+        .bind(size)
+        .bind(dimensions.map(|(width, _)| width as i32))
+        .bind(dimensions.map(|(_, height)| height as i32))
+        .execute(db)
+        .await?
+        .last_insert_id() as i32;
+        Self::get_by_id(db, id)
+            .await
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    pub async fn delete(&self, db: &sqlx::MySqlPool) -> Result<(), Error> {
+        sqlx::query("DELETE FROM media_files WHERE id = ?")
+            .bind(self.id)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    // get the URL path the file is served under.
+    pub fn url(&self) -> String {
+        format!("{}/{}", MEDIA_URL_PREFIX, self.path)
+    }
+}
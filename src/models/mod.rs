@@ -1,13 +1,30 @@
 mod articles;
+mod autosaves;
+mod media_files;
 mod pages;
 mod users;
 
 pub(crate) use articles::*;
+pub(crate) use autosaves::*;
+pub(crate) use media_files::*;
 pub(crate) use pages::*;
 pub(crate) use users::*;
 
+use chrono::{DateTime, Utc};
+use sqlx::prelude::FromRow;
+use tracing::info;
+
 use crate::Error;
 
+// The aggregates of a table to validate the cached pages against, the total version changes on
+// every edit even if `updated_at` is kept.
+#[derive(FromRow, Default)]
+pub struct Revision {
+    pub count: i64,
+    pub total_version: i64,
+    pub latest_updated: Option<DateTime<Utc>>,
+}
+
 const CREATE_TABLE_ARTICLES_SQL: &str = r#"
 CREATE TABLE IF NOT EXISTS articles (
     id INT AUTO_INCREMENT PRIMARY KEY,
@@ -15,7 +32,11 @@ CREATE TABLE IF NOT EXISTS articles (
     content TEXT NOT NULL,
     tags VARCHAR(255) NOT NULL,
     created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    version INT NOT NULL DEFAULT 0,
+    cover_image VARCHAR(1024),
+    summary TEXT,
+    hide_toc BOOLEAN NOT NULL DEFAULT FALSE
 ) CHARSET = utf8mb4;
 "#;
 
@@ -36,7 +57,8 @@ CREATE TABLE IF NOT EXISTS pages (
     title TEXT NOT NULL,
     content TEXT NOT NULL,
     created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    version INT NOT NULL DEFAULT 0
 ) CHARSET = utf8mb4;
 "#;
 
@@ -50,6 +72,35 @@ CREATE TABLE IF NOT EXISTS users (
 ) CHARSET = utf8mb4;
 "#;
 
+// `entity_id` is 0 for the new article or page, since the unique key treats NULLs as distinct.
+const CREATE_TABLE_AUTOSAVES_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS autosaves (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(255) NOT NULL,
+    entity_type VARCHAR(32) NOT NULL,
+    entity_id INT NOT NULL DEFAULT 0,
+    title TEXT NOT NULL,
+    tags VARCHAR(255) NOT NULL,
+    content TEXT NOT NULL,
+    saved_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY (username, entity_type, entity_id)
+) CHARSET = utf8mb4;
+"#;
+
+const CREATE_TABLE_MEDIA_FILES_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS media_files (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    path VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    mime_type VARCHAR(255) NOT NULL,
+    size BIGINT NOT NULL,
+    width INT,
+    height INT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY (path)
+) CHARSET = utf8mb4;
+"#;
+
 pub async fn create_tables_within_transaction(db: &sqlx::MySqlPool) -> Result<(), Error> {
     let mut tx = db.begin().await?;
 
@@ -63,6 +114,55 @@ pub async fn create_tables_within_transaction(db: &sqlx::MySqlPool) -> Result<()
     sqlx::query(CREATE_TABLE_USERS_SQL)
         .execute(&mut *tx)
         .await?;
+    sqlx::query(CREATE_TABLE_AUTOSAVES_SQL)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(CREATE_TABLE_MEDIA_FILES_SQL)
+        .execute(&mut *tx)
+        .await?;
+    // migrate the tables created by the previous versions.
+    for table in ["articles", "pages"] {
+        add_column_if_not_exists(&mut tx, table, "version", "INT NOT NULL DEFAULT 0").await?;
+    }
+    add_column_if_not_exists(&mut tx, "articles", "cover_image", "VARCHAR(1024)").await?;
+    add_column_if_not_exists(&mut tx, "articles", "summary", "TEXT").await?;
+    add_column_if_not_exists(
+        &mut tx,
+        "articles",
+        "hide_toc",
+        "BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .await?;
+    for column in ["width", "height"] {
+        add_column_if_not_exists(&mut tx, "media_files", column, "INT").await?;
+    }
 
     tx.commit().await.map_err(|e| e.into())
 }
+
+// Add the column to the existing table if it doesn't exist yet.
+async fn add_column_if_not_exists(
+    tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), Error> {
+    let exists = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?",
+    )
+    .bind(table)
+    .bind(column)
+    .fetch_one(&mut **tx)
+    .await?
+        > 0;
+    if !exists {
+        info!("adding column {} to table {}", column, table);
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
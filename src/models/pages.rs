@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 
 use crate::{
+    models::Revision,
     utils::{Editable, EditorForm},
     Error,
 };
@@ -17,6 +18,12 @@ pub struct Page {
     content: String,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
+    // increased on every update to detect the concurrent edits.
+    version: i32,
+    // the version the editor was based on, `None` means updating unconditionally.
+    #[sqlx(skip)]
+    #[serde(skip)]
+    base_version: Option<i32>,
 }
 
 impl Page {
@@ -34,6 +41,16 @@ impl Page {
             .unwrap_or_default()
     }
 
+    // get the count, the sum of the versions and the latest update in one query.
+    pub async fn get_revision(db: &sqlx::MySqlPool) -> Revision {
+        sqlx::query_as(
+            "SELECT COUNT(*) AS count, CAST(COALESCE(SUM(version), 0) AS SIGNED) AS total_version, MAX(updated_at) AS latest_updated FROM pages",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap_or_default()
+    }
+
     pub async fn get_by_id(db: &sqlx::MySqlPool, id: i32) -> Option<Self> {
         sqlx::query_as("SELECT * FROM pages WHERE id = ?")
             .bind(id)
@@ -78,6 +95,20 @@ impl Display for Page {
 
 #[async_trait]
 impl Editable for Page {
+    const KIND: &'static str = "page";
+
+    fn id(&self) -> Option<i32> {
+        self.id
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    async fn get_latest(db: &sqlx::MySqlPool, id: i32) -> Option<Self> {
+        Self::get_by_id(db, id).await
+    }
+
     fn get_redirect_url(&self) -> String {
         format!("/{}", self.title.to_lowercase())
     }
@@ -97,16 +128,29 @@ impl Editable for Page {
             }
         }
 
-        sqlx::query("UPDATE pages SET title = ?, content = ? WHERE id = ?")
-            .bind(&self.title)
-            .bind(&self.content)
-            .bind(id)
-            .execute(&mut *tx)
-            .await?;
+        let result = sqlx::query(
+            "UPDATE pages SET title = ?, content = ?, version = version + 1 WHERE id = ? AND (? IS NULL OR version = ?)",
+        )
+        .bind(&self.title)
+        .bind(&self.content)
+        .bind(id)
+        .bind(self.base_version)
+        .bind(self.base_version)
+        .execute(&mut *tx)
+        .await?;
+        // nothing is updated if the page has been modified since the editor loaded it.
+        if result.rows_affected() == 0 {
+            return match Self::get_by_id(db, id).await {
+                Some(_) => Err(Error::EditConflict(id)),
+                None => Err(sqlx::Error::RowNotFound.into()),
+            };
+        }
 
         tx.commit().await?;
 
-        Ok(Self::get_by_id(db, id).await.unwrap())
+        Self::get_by_id(db, id)
+            .await
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
     }
 
     async fn insert(&self, db: &sqlx::MySqlPool) -> Result<Self, Error> {
@@ -132,7 +176,9 @@ impl Editable for Page {
 
         tx.commit().await?;
 
-        Ok(Self::get_by_id(db, id).await.unwrap())
+        Self::get_by_id(db, id)
+            .await
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
     }
 
     async fn delete(&self, db: &sqlx::MySqlPool) -> Result<(), Error> {
@@ -154,6 +200,7 @@ impl From<EditorForm> for Page {
             id: form.id,
             title: form.title.unwrap_or_default().trim().to_string(),
             content: form.content.unwrap_or_default(),
+            base_version: form.version,
             ..Default::default()
         }
     }
@@ -0,0 +1,388 @@
+use std::{path::PathBuf, sync::Arc};
+
+use ab_glyph::{point, Font, FontArc, GlyphId, PxScale, ScaleFont};
+use axum::{
+    extract::State,
+    http::{header::CACHE_CONTROL, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{codecs::png::PngEncoder, Rgb, RgbImage};
+use tracing::{info, warn};
+
+use crate::{
+    app::AppState,
+    config::Config,
+    media::{is_not_modified, media_response, write_cache},
+    models::Article,
+    utils::{format_datetime, Path, DEFAULT_DATE_FORMAT},
+    Error,
+};
+
+// The size recommended by the Open Graph and Twitter cards.
+const CARD_WIDTH: u32 = 1200;
+const CARD_HEIGHT: u32 = 630;
+const PADDING: f32 = 80.0;
+const ACCENT_WIDTH: u32 = 16;
+const TITLE_SIZE: f32 = 68.0;
+const TITLE_MAX_LINES: usize = 4;
+const META_SIZE: f32 = 32.0;
+
+const BACKGROUND: Rgb<u8> = Rgb([0x1f, 0x23, 0x28]);
+const FOREGROUND: Rgb<u8> = Rgb([0xf5, 0xf5, 0xf5]);
+const MUTED: Rgb<u8> = Rgb([0x9a, 0xa4, 0xae]);
+const ACCENT: Rgb<u8> = Rgb([0x3b, 0x82, 0xf6]);
+
+// The DejaVu Sans fonts are embedded so that the cards render the same anywhere, see `fonts/LICENSE`.
+static REGULAR_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+static BOLD_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans-Bold.ttf");
+
+// The texts on the social card of an article.
+pub struct Card {
+    pub blog_name: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub date: String,
+}
+
+// The fonts to render the social cards with and the directory caching them.
+#[derive(Clone)]
+pub struct OgImages {
+    regular: FontArc,
+    bold: FontArc,
+    cache_dir: PathBuf,
+}
+
+impl OgImages {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let (regular, bold) = match config.og_image_font() {
+            // the font overriding the embedded ones is used for all the texts.
+            Some(path) => {
+                let font = FontArc::try_from_vec(std::fs::read(path)?).map_err(|_| {
+                    Error::ConfigValidation(format!("invalid og_image config, bad font {}", path))
+                })?;
+                (font.clone(), font)
+            }
+            None => (
+                FontArc::try_from_slice(REGULAR_FONT).expect("embedded font is valid"),
+                FontArc::try_from_slice(BOLD_FONT).expect("embedded font is valid"),
+            ),
+        };
+        Ok(Self {
+            regular,
+            bold,
+            cache_dir: PathBuf::from(config.og_image_cache_dir()),
+        })
+    }
+
+    // Render the card into a PNG image.
+    pub fn render(&self, card: &Card) -> Result<Vec<u8>, Error> {
+        let mut image = RgbImage::from_pixel(CARD_WIDTH, CARD_HEIGHT, BACKGROUND);
+        for x in 0..ACCENT_WIDTH {
+            for y in 0..CARD_HEIGHT {
+                image.put_pixel(x, y, ACCENT);
+            }
+        }
+        let max_width = CARD_WIDTH as f32 - PADDING * 2.0;
+
+        let blog_name = truncate_line(&self.regular, META_SIZE, &card.blog_name, max_width);
+        draw_text(
+            &mut image,
+            &self.regular,
+            META_SIZE,
+            (PADDING, PADDING + META_SIZE),
+            ACCENT,
+            &blog_name,
+        );
+
+        // the title is centered vertically between the blog name and the meta lines.
+        let lines = wrap_text(
+            &self.bold,
+            TITLE_SIZE,
+            &card.title,
+            max_width,
+            TITLE_MAX_LINES,
+        );
+        let line_height = TITLE_SIZE * 1.25;
+        let (top, bottom) = (
+            PADDING + META_SIZE * 2.0,
+            CARD_HEIGHT as f32 - PADDING - META_SIZE * 3.0,
+        );
+        let mut baseline =
+            top + ((bottom - top) - line_height * lines.len() as f32).max(0.0) / 2.0 + TITLE_SIZE;
+        for line in &lines {
+            draw_text(
+                &mut image,
+                &self.bold,
+                TITLE_SIZE,
+                (PADDING, baseline),
+                FOREGROUND,
+                line,
+            );
+            baseline += line_height;
+        }
+
+        let tags = card
+            .tags
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let tags = truncate_line(&self.regular, META_SIZE, &tags, max_width);
+        draw_text(
+            &mut image,
+            &self.regular,
+            META_SIZE,
+            (PADDING, CARD_HEIGHT as f32 - PADDING - META_SIZE * 1.5),
+            MUTED,
+            &tags,
+        );
+        draw_text(
+            &mut image,
+            &self.regular,
+            META_SIZE,
+            (PADDING, CARD_HEIGHT as f32 - PADDING),
+            MUTED,
+            &card.date,
+        );
+
+        let mut bytes = Vec::new();
+        image.write_with_encoder(PngEncoder::new(&mut bytes))?;
+        Ok(bytes)
+    }
+
+    // The cached card is named after the version of the article, so any update invalidates it.
+    fn cache_path(&self, id: i32, version: i32) -> PathBuf {
+        self.cache_dir.join(format!("{}-{}.png", id, version))
+    }
+
+    // Remove the cards of the older versions of the article.
+    async fn remove_stale(&self, id: i32, version: i32) -> Result<(), Error> {
+        let prefix = format!("{}-", id);
+        let current = format!("{}-{}.png", id, version);
+        let mut entries = tokio::fs::read_dir(&self.cache_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(&prefix) && name.ends_with(".png") && name != current {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn text_width(font: &FontArc, size: f32, text: &str) -> f32 {
+    let font = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let glyph_id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph_id);
+        }
+        width += font.h_advance(glyph_id);
+        previous = Some(glyph_id);
+    }
+    width
+}
+
+// Draw a line of text with its baseline at `y`, blending the glyph coverage into the background.
+fn draw_text(
+    image: &mut RgbImage,
+    font: &FontArc,
+    size: f32,
+    (x, y): (f32, f32),
+    color: Rgb<u8>,
+    text: &str,
+) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut caret = x;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, glyph_id);
+        }
+        let glyph = glyph_id.with_scale_and_position(size, point(caret, y));
+        caret += scaled.h_advance(glyph_id);
+        previous = Some(glyph_id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= image.width() as i32 || py >= image.height() as i32 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            let coverage = coverage.clamp(0.0, 1.0);
+            for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+                *channel =
+                    (*channel as f32 * (1.0 - coverage) + target as f32 * coverage).round() as u8;
+            }
+        });
+    }
+}
+
+// Wrap the text into the lines fitting the width, the words too long to fit are broken anywhere,
+// e.g. the CJK texts without spaces. The lines beyond the max are cut off with an ellipsis.
+fn wrap_text(
+    font: &FontArc,
+    size: f32,
+    text: &str,
+    max_width: f32,
+    max_lines: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if text_width(font, size, &candidate) <= max_width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if text_width(font, size, &line) > max_width {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = lines.pop().unwrap_or_default();
+        lines.push(ellipsize(font, size, &format!("{}…", last), max_width));
+    }
+    lines
+}
+
+fn truncate_line(font: &FontArc, size: f32, text: &str, max_width: f32) -> String {
+    if text_width(font, size, text) <= max_width {
+        text.to_string()
+    } else {
+        ellipsize(font, size, text, max_width)
+    }
+}
+
+// Cut off the text to fit the width with a trailing ellipsis.
+fn ellipsize(font: &FontArc, size: f32, text: &str, max_width: f32) -> String {
+    let mut text = text.trim_end_matches('…').trim_end().to_string();
+    while !text.is_empty() && text_width(font, size, &format!("{}…", text)) > max_width {
+        text.pop();
+        text = text.trim_end().to_string();
+    }
+    format!("{}…", text)
+}
+
+// Serve the social card of the article, which is generated on the first request after any update
+// and cached on the local disk.
+pub async fn handler_og_image(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    if !state.config.og_image_enabled() {
+        return Err(Error::NotFound);
+    }
+    let article = Article::get_by_id(&state.db, id)
+        .await
+        .ok_or(Error::NotFound)?;
+    let etag = format!("\"{}-{}\"", id, article.version);
+    if is_not_modified(&headers, &etag) {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+
+    let og_images = &state.og_images;
+    let cache_path = og_images.cache_path(id, article.version);
+    let bytes = match tokio::fs::read(&cache_path).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let card = Card {
+                blog_name: state.config.blog_name(),
+                title: article.title.clone(),
+                tags: article
+                    .tags
+                    .split(',')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect(),
+                date: format_datetime(
+                    &article.created_at.with_timezone(&state.config.timezone()),
+                    DEFAULT_DATE_FORMAT,
+                )
+                .unwrap_or_default(),
+            };
+            let renderer = og_images.clone();
+            let bytes = tokio::task::spawn_blocking(move || renderer.render(&card)).await??;
+            // serve the card even if it can't be cached, and the stale ones are only left behind.
+            match write_cache(&cache_path, &bytes).await {
+                Ok(()) => info!("generated social card {}", cache_path.display()),
+                Err(err) => warn!("failed caching {}: {}", cache_path.display(), err),
+            }
+            if let Err(err) = og_images.remove_stale(id, article.version).await {
+                warn!(
+                    "failed removing the stale social cards of article {}: {:?}",
+                    id, err
+                );
+            }
+            bytes
+        }
+        Err(err) => return Err(err.into()),
+    };
+    // the URL stays the same across the updates, so it's revalidated like the article page.
+    let mut response = media_response(&state, bytes, "image/png", Some(etag));
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, state.config.pages_cache_control());
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::test_config;
+
+    #[test]
+    fn test_render_card() {
+        let config = test_config("", "");
+        let og_images = OgImages::new(&config).unwrap();
+
+        let title = "A fairly long title of an article that has to be wrapped into several lines";
+        let lines = wrap_text(&og_images.bold, TITLE_SIZE, title, 1040.0, TITLE_MAX_LINES);
+        assert!(lines.len() > 1);
+        assert_eq!(lines.join(" "), title);
+        // the words too long are broken and the overflowing lines are cut off.
+        let lines = wrap_text(&og_images.bold, TITLE_SIZE, &"x".repeat(200), 1040.0, 2);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with('…'));
+        assert!(lines
+            .iter()
+            .all(|line| text_width(&og_images.bold, TITLE_SIZE, line) <= 1040.0));
+
+        let png = og_images
+            .render(&Card {
+                blog_name: "rsomhaP".to_string(),
+                title: title.to_string(),
+                tags: vec!["rust".to_string(), "blog".to_string()],
+                date: "2024-08-22".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            crate::images::image_dimensions(&png),
+            Some((CARD_WIDTH, CARD_HEIGHT))
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{config::Config, Error};
+
+pub const BUILTIN_TEMPLATES_DIR: &str = "templates";
+pub const BUILTIN_STATIC_DIR: &str = "static";
+const BUILTIN_THEME_NAME: &str = "default";
+const MANIFEST_FILE_NAME: &str = "theme.toml";
+
+// The manifest of a theme, i.e. the `theme.toml` file under the theme directory.
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    name: Option<String>,
+    version: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    // the default values of the theme options, which can be overridden by `[theme.options]` in the config.
+    #[serde(default)]
+    defaults: toml::Table,
+}
+
+// A theme is a directory containing the `templates` and `static` directories and a manifest,
+// any template or static file missing in it falls back to the built-in one.
+#[derive(Clone, Debug, Serialize)]
+pub struct Theme {
+    name: String,
+    version: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    options: toml::Table,
+    // the root directory of the theme, `None` means the built-in theme.
+    #[serde(skip)]
+    dir: Option<PathBuf>,
+}
+
+impl Theme {
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        match config.theme_name() {
+            Some(name) => Self::from_dir(
+                &Path::new(&config.themes_dir()).join(name),
+                config.theme_options(),
+            ),
+            None => Ok(Self {
+                name: BUILTIN_THEME_NAME.to_string(),
+                version: None,
+                author: None,
+                description: None,
+                options: config.theme_options().clone(),
+                dir: None,
+            }),
+        }
+    }
+
+    fn from_dir(dir: &Path, options: &toml::Table) -> Result<Self, Error> {
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        if !manifest_path.is_file() {
+            return Err(Error::ConfigValidation(format!(
+                "invalid theme config, {} is not found",
+                manifest_path.display()
+            )));
+        }
+        let manifest: Manifest = toml::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+        // the options in the config take precedence over the defaults of the theme.
+        let mut merged_options = manifest.defaults;
+        merged_options.extend(options.clone());
+        let name = manifest.name.unwrap_or_else(|| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        info!("loaded theme {} from {}", name, dir.display());
+
+        Ok(Self {
+            name,
+            version: manifest.version,
+            author: manifest.author,
+            description: manifest.description,
+            options: merged_options,
+            dir: Some(dir.to_path_buf()),
+        })
+    }
+
+    // get the directories to look up the templates, in the descending order of priority.
+    pub fn template_dirs(&self) -> Vec<PathBuf> {
+        self.dirs("templates", BUILTIN_TEMPLATES_DIR)
+    }
+
+    // get the directories to look up the static files, in the descending order of priority.
+    pub fn static_dirs(&self) -> Vec<PathBuf> {
+        self.dirs("static", BUILTIN_STATIC_DIR)
+    }
+
+    fn dirs(&self, sub_dir: &str, builtin_dir: &str) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+        if let Some(dir) = &self.dir {
+            let dir = dir.join(sub_dir);
+            if dir.is_dir() {
+                dirs.push(dir);
+            }
+        }
+        dirs.push(PathBuf::from(builtin_dir));
+        dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_theme_from_dir() {
+        let dir = std::env::temp_dir().join(format!("rsomhap-theme-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("templates")).unwrap();
+        std::fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            "name = \"Paper\"\n[defaults]\naccent_color = \"red\"\nshow_avatar = true\n",
+        )
+        .unwrap();
+
+        let options = toml::from_str::<toml::Table>("accent_color = \"blue\"").unwrap();
+        let theme = Theme::from_dir(&dir, &options).unwrap();
+        assert_eq!(theme.name, "Paper");
+        assert_eq!(theme.options["accent_color"].as_str(), Some("blue"));
+        assert_eq!(theme.options["show_avatar"].as_bool(), Some(true));
+        // the theme has no static directory so only the built-in one is used.
+        assert_eq!(
+            theme.template_dirs(),
+            vec![dir.join("templates"), PathBuf::from(BUILTIN_TEMPLATES_DIR)]
+        );
+        assert_eq!(theme.static_dirs(), vec![PathBuf::from(BUILTIN_STATIC_DIR)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Theme::from_dir(&dir, &options).is_err());
+    }
+}
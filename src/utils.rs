@@ -1,14 +1,17 @@
-use std::{collections::HashSet, fmt::Display, sync::Arc};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Write},
+    sync::Arc,
+};
 
 use axum::{
     async_trait,
     extract::{rejection::PathRejection, FromRef, FromRequest, FromRequestParts, Request},
     http::request::Parts,
-    response::Html,
     RequestExt,
 };
-use minijinja::context;
-use serde::{de::DeserializeOwned, Deserialize};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::error;
 
 use crate::app::AppState;
@@ -17,14 +20,14 @@ use crate::Error;
 #[macro_export]
 macro_rules! render_template_with_context {
     ($state:expr, $template_name:expr $(,)?) => {
-        Html($state.render_template($template_name, context! {}).await)
+        Html($state.render_template($template_name, context! {}).await?)
     };
     ($state:expr, $template_name:expr, $context:expr $(,)?) => {
-        Html($state.render_template($template_name, $context).await)
+        Html($state.render_template($template_name, $context).await?)
     };
 }
 
-// A wrapper for `axum::extract::Path` that can render a 404 page if the path is rejected.
+// A wrapper for `axum::extract::Path` that responds with a 404 page if the path is rejected.
 pub struct Path<T>(pub T);
 
 #[async_trait]
@@ -36,24 +39,18 @@ where
     T: Send,
     S: Send + Sync,
 {
-    type Rejection = Html<String>;
+    type Rejection = Error;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         match axum::extract::Path::<T>::from_request_parts(parts, state).await {
             Ok(value) => Ok(Self(value.0)),
             Err(rejection) => {
                 error!("parse path rejection: {:?}", rejection);
-                // get the app state.
-                let state = Arc::<AppState>::from_ref(state);
-                // render the template.
-                Err(render_template_with_context!(
-                    state,
-                    "error.html",
-                    context! {
-                        title => "404",
-                        message => "Oops, it seems like you've stumbled upon a URL that doesn't exist...",
-                    },
-                ))
+                match rejection {
+                    // the path doesn't match the expected type, e.g. a non-numeric ID.
+                    PathRejection::FailedToDeserializePathParams(_) => Err(Error::NotFound),
+                    _ => Err(Error::BadRequest(rejection.body_text())),
+                }
             }
         }
     }
@@ -70,10 +67,28 @@ pub struct EditorForm {
     pub title: Option<String>,
     pub tags: Option<String>,
     pub content: Option<String>,
+    // the publish date in RFC 3339 format, the one from the editor is in the configured timezone
+    // and normalized by the `Entity` extractor.
+    pub created_at: Option<String>,
+    // keep `updated_at` unchanged for the minor edits if present, e.g. the checkbox is checked.
+    pub keep_updated_at: Option<String>,
+    // the version loaded by the editor to detect the concurrent edits.
+    pub version: Option<i32>,
+    // the social card fields of the articles, `None` means unchanged and an empty one clears it.
+    pub cover_image: Option<String>,
+    pub summary: Option<String>,
+    // hide the automatic table of contents of the articles, `None` means unchanged.
+    pub hide_toc: Option<bool>,
 }
 
 #[async_trait]
-pub trait Editable: DeserializeOwned + Display {
+pub trait Editable: DeserializeOwned + Serialize + Display {
+    // the kind of the entity, e.g. to key the autosaves.
+    const KIND: &'static str;
+    fn id(&self) -> Option<i32>;
+    fn updated_at(&self) -> DateTime<Utc>;
+    // get the latest saved one, e.g. to show the conflict with the concurrent edits.
+    async fn get_latest(db: &sqlx::MySqlPool, id: i32) -> Option<Self>;
     fn get_redirect_url(&self) -> String;
     async fn update(&self, db: &sqlx::MySqlPool) -> Result<Self, Error>;
     async fn insert(&self, db: &sqlx::MySqlPool) -> Result<Self, Error>;
@@ -92,7 +107,7 @@ where
     T: Editable + From<EditorForm>,
     S: Send + Sync,
 {
-    type Rejection = Html<String>;
+    type Rejection = Error;
 
     async fn from_request(mut req: Request, state: &S) -> Result<Self, Self::Rejection> {
         // first get the path from the request to ensure we can determine if the entity is new by checking if the ID is present.
@@ -100,7 +115,7 @@ where
             .extract_parts_with_state::<Path<EditorPath>, S>(state)
             .await?;
         // extract the form from the request, this will consume the request.
-        let form = match axum::extract::Form::<EditorForm>::from_request(req, state).await {
+        let mut form = match axum::extract::Form::<EditorForm>::from_request(req, state).await {
             Ok(mut form) => {
                 // set the ID from the parsed path
                 form.id = path.0.id;
@@ -108,23 +123,81 @@ where
             }
             Err(rejection) => {
                 error!("parse form rejection: {:?}", rejection);
-                let app_state = Arc::<AppState>::from_ref(state);
-                return Err(render_template_with_context!(
-                    app_state,
-                    "error.html",
-                    context! {
-                        title => "Error",
-                        message => "Oops, it seems like something went wrong during the posting...",
-                    }
-                ));
+                return Err(Error::BadRequest(rejection.body_text()));
             }
         };
+        // interpret the publish date in the configured timezone, an empty one means unchanged.
+        if let Some(created_at) = form
+            .created_at
+            .take()
+            .filter(|value| !value.trim().is_empty())
+        {
+            let app_state = Arc::<AppState>::from_ref(state);
+            match parse_local_datetime(&created_at, app_state.config.timezone()) {
+                Some(created_at) => form.created_at = Some(created_at.to_rfc3339()),
+                None => {
+                    return Err(Error::BadRequest(format!(
+                        "invalid publish date {}",
+                        created_at
+                    )))
+                }
+            }
+        }
         let is_new = form.id.is_none();
         let entity = T::from(form.0);
         Ok(Entity { entity, is_new })
     }
 }
 
+// The default format of the `date` filter, e.g. `2024-08-22`.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Format the datetime with the strftime format, `rfc3339` and `rfc2822` are also supported
+// for the feeds. An invalid format is an error rather than a panic of `to_string`.
+pub fn format_datetime<Tz: TimeZone>(
+    datetime: &DateTime<Tz>,
+    format: &str,
+) -> Result<String, fmt::Error>
+where
+    Tz::Offset: fmt::Display,
+{
+    match format {
+        "rfc3339" => Ok(datetime.to_rfc3339_opts(SecondsFormat::Secs, false)),
+        "rfc2822" => Ok(datetime.to_rfc2822()),
+        format => {
+            let mut formatted = String::new();
+            write!(formatted, "{}", datetime.format(format))?;
+            Ok(formatted)
+        }
+    }
+}
+
+// Parse the datetime in RFC 3339 format, or the local one without the offset in the timezone,
+// e.g. `2024-08-22T09:30` from the `datetime-local` input.
+pub fn parse_local_datetime(value: &str, timezone: chrono_tz::Tz) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
 // Sort out tags and remove duplicates.
 pub fn sort_out_tags(tags: &str) -> String {
     let mut tags = tags
@@ -140,7 +213,7 @@ pub fn sort_out_tags(tags: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::sort_out_tags;
+    use super::{format_datetime, parse_local_datetime, sort_out_tags};
 
     #[test]
     fn test_organize_tags() {
@@ -167,4 +240,48 @@ mod tests {
         assert_eq!(sort_out_tags(""), "");
         assert_eq!(sort_out_tags(",,,,"), "");
     }
+
+    #[test]
+    fn test_format_datetime() {
+        let datetime = "2024-08-22T20:06:04Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+            .with_timezone(&chrono_tz::Asia::Shanghai);
+        assert_eq!(
+            format_datetime(&datetime, "%Y-%m-%d %H:%M").unwrap(),
+            "2024-08-23 04:06"
+        );
+        assert_eq!(
+            format_datetime(&datetime, "rfc3339").unwrap(),
+            "2024-08-23T04:06:04+08:00"
+        );
+        assert_eq!(
+            format_datetime(&datetime, "rfc2822").unwrap(),
+            "Fri, 23 Aug 2024 04:06:04 +0800"
+        );
+        // an invalid format is an error rather than a panic.
+        assert!(format_datetime(&datetime, "%Q").is_err());
+    }
+
+    #[test]
+    fn test_parse_local_datetime() {
+        let timezone = chrono_tz::Asia::Shanghai;
+        let expected = "2024-08-22T01:30:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .ok();
+        assert_eq!(parse_local_datetime("2024-08-22T09:30", timezone), expected);
+        assert_eq!(
+            parse_local_datetime("2024-08-22 09:30:00", timezone),
+            expected
+        );
+        assert_eq!(
+            parse_local_datetime("2024-08-22T01:30:00Z", timezone),
+            expected
+        );
+        assert_eq!(
+            parse_local_datetime("2024-08-22", timezone),
+            "2024-08-21T16:00:00Z".parse().ok()
+        );
+        assert_eq!(parse_local_datetime("yesterday", timezone), None);
+    }
 }
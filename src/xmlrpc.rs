@@ -0,0 +1,692 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use axum_login::AuthnBackend;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use quick_xml::{escape::escape, events::Event, Reader};
+use tracing::{error, info};
+
+use crate::{
+    app::AppState,
+    auth::Credentials,
+    media::save_media,
+    models::{Article, Tags},
+    utils::{Editable, EditorForm},
+    Error,
+};
+
+// The blog ID returned to the clients, there is only one blog in rsomhaP.
+const BLOG_ID: &str = "1";
+
+// The fault codes returned to the clients.
+const FAULT_PARSE_ERROR: i32 = -32700;
+const FAULT_METHOD_NOT_FOUND: i32 = -32601;
+const FAULT_INVALID_PARAMS: i32 = -32602;
+const FAULT_INTERNAL_ERROR: i32 = -32603;
+const FAULT_UNAUTHORIZED: i32 = 403;
+const FAULT_NOT_FOUND: i32 = 404;
+
+// The max nesting depth of the XML elements, which bounds the recursion of parsing the values.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+enum XmlRpcValue {
+    Int(i32),
+    Bool(bool),
+    Double(f64),
+    String(String),
+    DateTime(DateTime<Utc>),
+    Base64(Vec<u8>),
+    Struct(Vec<(String, XmlRpcValue)>),
+    Array(Vec<XmlRpcValue>),
+}
+
+impl XmlRpcValue {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_i32(&self) -> Option<i32> {
+        match self {
+            Self::Int(value) => Some(*value),
+            // some clients send the IDs and numbers as strings.
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&XmlRpcValue> {
+        match self {
+            Self::Struct(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push_str("<value>");
+        match self {
+            Self::Int(value) => out.push_str(&format!("<int>{}</int>", value)),
+            Self::Bool(value) => out.push_str(&format!("<boolean>{}</boolean>", *value as i32)),
+            Self::Double(value) => out.push_str(&format!("<double>{}</double>", value)),
+            Self::String(value) => out.push_str(&format!("<string>{}</string>", escape(value))),
+            Self::DateTime(value) => out.push_str(&format!(
+                "<dateTime.iso8601>{}</dateTime.iso8601>",
+                value.format("%Y%m%dT%H:%M:%S")
+            )),
+            Self::Base64(value) => {
+                out.push_str(&format!("<base64>{}</base64>", STANDARD.encode(value)))
+            }
+            Self::Struct(members) => {
+                out.push_str("<struct>");
+                for (name, value) in members {
+                    out.push_str(&format!("<member><name>{}</name>", escape(name)));
+                    value.write(out);
+                    out.push_str("</member>");
+                }
+                out.push_str("</struct>");
+            }
+            Self::Array(values) => {
+                out.push_str("<array><data>");
+                for value in values {
+                    value.write(out);
+                }
+                out.push_str("</data></array>");
+            }
+        }
+        out.push_str("</value>");
+    }
+}
+
+// A minimal XML element tree, which is enough to parse the XML-RPC method calls.
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+fn parse_xml(body: &[u8]) -> Result<Node, String> {
+    let mut reader = Reader::from_reader(body);
+    // the stack of the elements being parsed, the bottom one is a virtual root.
+    let mut stack = vec![Node::default()];
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                if stack.len() > MAX_DEPTH {
+                    return Err("too deeply nested".to_string());
+                }
+                stack.push(Node {
+                    name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+                    ..Default::default()
+                })
+            }
+            Ok(Event::Empty(start)) => {
+                let node = Node {
+                    name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+                    ..Default::default()
+                };
+                stack.last_mut().unwrap().children.push(node);
+            }
+            Ok(Event::End(_)) => {
+                let node = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Err("unexpected closing tag".to_string()),
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let text = text.unescape().map_err(|e| e.to_string())?;
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Ok(Event::CData(cdata)) => {
+                let text = String::from_utf8_lossy(&cdata.into_inner()).into_owned();
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    match stack.pop() {
+        Some(root) if stack.is_empty() => Ok(root),
+        _ => Err("unclosed tags".to_string()),
+    }
+}
+
+fn parse_value(node: &Node, depth: usize) -> Result<XmlRpcValue, String> {
+    if depth > MAX_DEPTH {
+        return Err("too deeply nested".to_string());
+    }
+    // a value without a type element is a string.
+    let typed = match node.children.first() {
+        Some(typed) => typed,
+        None => return Ok(XmlRpcValue::String(node.text.clone())),
+    };
+    let text = typed.text.trim();
+    match typed.name.as_str() {
+        "i4" | "int" => text
+            .parse()
+            .map(XmlRpcValue::Int)
+            .map_err(|_| format!("invalid int {}", text)),
+        "boolean" => Ok(XmlRpcValue::Bool(text == "1")),
+        "double" => text
+            .parse()
+            .map(XmlRpcValue::Double)
+            .map_err(|_| format!("invalid double {}", text)),
+        "string" => Ok(XmlRpcValue::String(typed.text.clone())),
+        "dateTime.iso8601" => parse_iso8601(text)
+            .map(XmlRpcValue::DateTime)
+            .ok_or_else(|| format!("invalid dateTime.iso8601 {}", text)),
+        "base64" => STANDARD
+            .decode(text.split_whitespace().collect::<String>())
+            .map(XmlRpcValue::Base64)
+            .map_err(|e| e.to_string()),
+        "struct" => typed
+            .children
+            .iter()
+            .filter(|member| member.name == "member")
+            .map(|member| {
+                let name = member.child("name").map(|name| name.text.clone());
+                let value = member
+                    .child("value")
+                    .map(|value| parse_value(value, depth + 1));
+                match (name, value) {
+                    (Some(name), Some(value)) => Ok((name, value?)),
+                    _ => Err("invalid struct member".to_string()),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(XmlRpcValue::Struct),
+        "array" => typed
+            .child("data")
+            .map(|data| {
+                data.children
+                    .iter()
+                    .map(|value| parse_value(value, depth + 1))
+                    .collect()
+            })
+            .unwrap_or(Ok(vec![]))
+            .map(XmlRpcValue::Array),
+        name => Err(format!("unsupported type {}", name)),
+    }
+}
+
+// Parse the `dateTime.iso8601` value, which is usually in the form of `20240101T12:00:00`.
+fn parse_iso8601(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            ["%Y%m%dT%H:%M:%S", "%Y%m%dT%H%M%S", "%Y-%m-%dT%H:%M:%S"]
+                .iter()
+                .find_map(|format| chrono::NaiveDateTime::parse_from_str(text, format).ok())
+                .map(|datetime| datetime.and_utc())
+        })
+}
+
+fn parse_method_call(body: &[u8]) -> Result<(String, Vec<XmlRpcValue>), String> {
+    let root = parse_xml(body)?;
+    let method_call = root.child("methodCall").ok_or("missing methodCall")?;
+    let method_name = method_call
+        .child("methodName")
+        .ok_or("missing methodName")?
+        .text
+        .trim()
+        .to_string();
+    let params = match method_call.child("params") {
+        Some(params) => params
+            .children
+            .iter()
+            .filter(|param| param.name == "param")
+            .map(|param| {
+                param
+                    .child("value")
+                    .map_or(Err("missing value".to_string()), |value| {
+                        parse_value(value, 0)
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+        None => vec![],
+    };
+    Ok((method_name, params))
+}
+
+struct Fault {
+    code: i32,
+    message: String,
+}
+
+impl Fault {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for Fault {
+    fn into_response(self) -> Response {
+        let mut out = String::from(r#"<?xml version="1.0"?><methodResponse><fault>"#);
+        XmlRpcValue::Struct(vec![
+            ("faultCode".to_string(), XmlRpcValue::Int(self.code)),
+            ("faultString".to_string(), XmlRpcValue::String(self.message)),
+        ])
+        .write(&mut out);
+        out.push_str("</fault></methodResponse>");
+        ([(CONTENT_TYPE, "text/xml; charset=utf-8")], out).into_response()
+    }
+}
+
+impl IntoResponse for XmlRpcValue {
+    fn into_response(self) -> Response {
+        let mut out = String::from(r#"<?xml version="1.0"?><methodResponse><params><param>"#);
+        self.write(&mut out);
+        out.push_str("</param></params></methodResponse>");
+        ([(CONTENT_TYPE, "text/xml; charset=utf-8")], out).into_response()
+    }
+}
+
+fn param(params: &[XmlRpcValue], index: usize) -> Result<&XmlRpcValue, Fault> {
+    params
+        .get(index)
+        .ok_or_else(|| Fault::new(FAULT_INVALID_PARAMS, format!("missing param {}", index)))
+}
+
+fn str_param(params: &[XmlRpcValue], index: usize) -> Result<&str, Fault> {
+    param(params, index)?.as_str().ok_or_else(|| {
+        Fault::new(
+            FAULT_INVALID_PARAMS,
+            format!("param {} must be a string", index),
+        )
+    })
+}
+
+fn int_param(params: &[XmlRpcValue], index: usize) -> Result<i32, Fault> {
+    param(params, index)?.as_i32().ok_or_else(|| {
+        Fault::new(
+            FAULT_INVALID_PARAMS,
+            format!("param {} must be an int", index),
+        )
+    })
+}
+
+// Authenticate the username and password in the params against the users table.
+async fn authenticate(
+    state: &AppState,
+    params: &[XmlRpcValue],
+    username_index: usize,
+) -> Result<(), Fault> {
+    let credentials = Credentials {
+        username: str_param(params, username_index)?.to_string(),
+        password: str_param(params, username_index + 1)?.to_string(),
+        next: None,
+    };
+    match state.authenticate(credentials).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Fault::new(
+            FAULT_UNAUTHORIZED,
+            "invalid username or password",
+        )),
+        Err(err) => {
+            error!("failed authenticating via XML-RPC: {:?}", err);
+            Err(Fault::new(FAULT_INTERNAL_ERROR, "failed to authenticate"))
+        }
+    }
+}
+
+async fn get_article(state: &AppState, id: i32) -> Result<Article, Fault> {
+    Article::get_by_id(&state.db, id)
+        .await
+        .ok_or_else(|| Fault::new(FAULT_NOT_FOUND, format!("post {} does not exist", id)))
+}
+
+// Map the post struct of MetaWeblog into the editor form, so it can go through the same paths as the editor.
+// The absent members are left as `None`, so that `editPost` can keep the stored values.
+fn post_to_form(id: Option<i32>, post: &XmlRpcValue) -> EditorForm {
+    let text = |key: &str| {
+        post.get(key)
+            .and_then(XmlRpcValue::as_str)
+            .map(str::to_string)
+    };
+    // the categories and keywords are both treated as tags.
+    let categories = match post.get("categories") {
+        Some(XmlRpcValue::Array(categories)) => Some(
+            categories
+                .iter()
+                .filter_map(XmlRpcValue::as_str)
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        ),
+        _ => None,
+    };
+    let tags = match (categories, text("mt_keywords")) {
+        (None, None) => None,
+        (categories, keywords) => Some(
+            categories
+                .unwrap_or_default()
+                .into_iter()
+                .chain(keywords)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    };
+    // some editors put the extended content into `mt_text_more`.
+    let content = match (text("description"), text("mt_text_more")) {
+        (Some(description), Some(more)) if !more.is_empty() => {
+            Some(format!("{}\n\n{}", description, more))
+        }
+        (description, _) => description,
+    };
+
+    EditorForm {
+        id,
+        title: text("title"),
+        tags,
+        content,
+        // backdate the post if the editor specifies the publish date.
+        created_at: match post.get("dateCreated") {
+            Some(XmlRpcValue::DateTime(created_at)) => Some(created_at.to_rfc3339()),
+            _ => None,
+        },
+        keep_updated_at: None,
+        version: None,
+        cover_image: None,
+        summary: text("mt_excerpt"),
+        hide_toc: None,
+    }
+}
+
+fn article_to_post(state: &AppState, article: &Article) -> XmlRpcValue {
+    let id = article.id.unwrap_or_default();
+    let link = format!(
+        "{}/article/{}",
+        state.config.blog_url().trim_end_matches('/'),
+        id
+    );
+    let categories = article
+        .tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| XmlRpcValue::String(tag.to_string()))
+        .collect();
+    XmlRpcValue::Struct(vec![
+        ("postid".to_string(), XmlRpcValue::String(id.to_string())),
+        (
+            "title".to_string(),
+            XmlRpcValue::String(article.title.clone()),
+        ),
+        (
+            "description".to_string(),
+            XmlRpcValue::String(article.content.clone()),
+        ),
+        ("categories".to_string(), XmlRpcValue::Array(categories)),
+        (
+            "mt_keywords".to_string(),
+            XmlRpcValue::String(article.tags.clone()),
+        ),
+        (
+            "dateCreated".to_string(),
+            XmlRpcValue::DateTime(article.created_at),
+        ),
+        ("link".to_string(), XmlRpcValue::String(link.clone())),
+        ("permaLink".to_string(), XmlRpcValue::String(link)),
+    ])
+}
+
+async fn dispatch(
+    state: &AppState,
+    method_name: &str,
+    params: &[XmlRpcValue],
+) -> Result<XmlRpcValue, Fault> {
+    match method_name {
+        // blogger.getUsersBlogs(appkey, username, password)
+        "blogger.getUsersBlogs" => {
+            authenticate(state, params, 1).await?;
+            Ok(XmlRpcValue::Array(vec![XmlRpcValue::Struct(vec![
+                (
+                    "blogid".to_string(),
+                    XmlRpcValue::String(BLOG_ID.to_string()),
+                ),
+                (
+                    "blogName".to_string(),
+                    XmlRpcValue::String(state.config.blog_name()),
+                ),
+                (
+                    "url".to_string(),
+                    XmlRpcValue::String(state.config.blog_url()),
+                ),
+                ("isAdmin".to_string(), XmlRpcValue::Bool(true)),
+            ])]))
+        }
+        // metaWeblog.getCategories(blogid, username, password)
+        "metaWeblog.getCategories" => {
+            authenticate(state, params, 1).await?;
+            Ok(XmlRpcValue::Array(
+                Tags::get_all_with_count(&state.db)
+                    .await
+                    .into_iter()
+                    .map(|tag| {
+                        XmlRpcValue::Struct(vec![
+                            (
+                                "categoryId".to_string(),
+                                XmlRpcValue::String(tag.name.clone()),
+                            ),
+                            ("title".to_string(), XmlRpcValue::String(tag.name.clone())),
+                            ("description".to_string(), XmlRpcValue::String(tag.name)),
+                        ])
+                    })
+                    .collect(),
+            ))
+        }
+        // metaWeblog.newPost(blogid, username, password, struct, publish)
+        "metaWeblog.newPost" => {
+            authenticate(state, params, 1).await?;
+            let article = Article::from(post_to_form(None, param(params, 3)?));
+            info!("inserting {} via XML-RPC", article);
+            match article.insert(&state.db).await {
+                Ok(article) => Ok(XmlRpcValue::String(
+                    article.id.unwrap_or_default().to_string(),
+                )),
+                Err(err) => {
+                    error!("failed inserting {} via XML-RPC: {:?}", article, err);
+                    Err(Fault::new(FAULT_INTERNAL_ERROR, "failed to save the post"))
+                }
+            }
+        }
+        // metaWeblog.editPost(postid, username, password, struct, publish)
+        "metaWeblog.editPost" => {
+            authenticate(state, params, 1).await?;
+            let current = get_article(state, int_param(params, 0)?).await?;
+            let mut form = post_to_form(current.id, param(params, 3)?);
+            // keep the stored title, tags and content if the client leaves them out.
+            form.title.get_or_insert(current.title);
+            form.tags.get_or_insert(current.tags);
+            form.content.get_or_insert(current.content);
+            let article = Article::from(form);
+            info!("updating {} via XML-RPC", article);
+            match article.update(&state.db).await {
+                Ok(_) => Ok(XmlRpcValue::Bool(true)),
+                Err(err) => {
+                    error!("failed updating {} via XML-RPC: {:?}", article, err);
+                    Err(Fault::new(FAULT_INTERNAL_ERROR, "failed to save the post"))
+                }
+            }
+        }
+        // metaWeblog.getPost(postid, username, password)
+        "metaWeblog.getPost" => {
+            authenticate(state, params, 1).await?;
+            let article = get_article(state, int_param(params, 0)?).await?;
+            Ok(article_to_post(state, &article))
+        }
+        // metaWeblog.getRecentPosts(blogid, username, password, numberOfPosts)
+        "metaWeblog.getRecentPosts" => {
+            authenticate(state, params, 1).await?;
+            let count = int_param(params, 3)?.max(1) as u32;
+            Ok(XmlRpcValue::Array(
+                Article::get_on_page(&state.db, 1, count)
+                    .await
+                    .iter()
+                    .map(|article| article_to_post(state, article))
+                    .collect(),
+            ))
+        }
+        // metaWeblog.newMediaObject(blogid, username, password, struct)
+        "metaWeblog.newMediaObject" => {
+            authenticate(state, params, 1).await?;
+            let media = param(params, 3)?;
+            let name = media
+                .get("name")
+                .and_then(XmlRpcValue::as_str)
+                .unwrap_or_default();
+            let bits = match media.get("bits") {
+                Some(XmlRpcValue::Base64(bits)) => bits,
+                _ => return Err(Fault::new(FAULT_INVALID_PARAMS, "missing the media bits")),
+            };
+            match save_media(state, name, bits).await {
+                Ok(media_file) => Ok(XmlRpcValue::Struct(vec![(
+                    "url".to_string(),
+                    XmlRpcValue::String(format!(
+                        "{}{}",
+                        state.config.blog_url().trim_end_matches('/'),
+                        media_file.url()
+                    )),
+                )])),
+                Err(err @ (Error::MediaTooLarge(_) | Error::MediaTypeNotAllowed(_))) => {
+                    Err(Fault::new(FAULT_INVALID_PARAMS, err.to_string()))
+                }
+                Err(err) => {
+                    error!("failed saving media {} via XML-RPC: {:?}", name, err);
+                    Err(Fault::new(FAULT_INTERNAL_ERROR, "failed to save the media"))
+                }
+            }
+        }
+        // blogger.deletePost(appkey, postid, username, password, publish)
+        "blogger.deletePost" => {
+            authenticate(state, params, 2).await?;
+            let article = get_article(state, int_param(params, 1)?).await?;
+            info!("deleting {} via XML-RPC", article);
+            match article.delete(&state.db).await {
+                Ok(()) => Ok(XmlRpcValue::Bool(true)),
+                Err(err) => {
+                    error!("failed deleting {} via XML-RPC: {:?}", article, err);
+                    Err(Fault::new(
+                        FAULT_INTERNAL_ERROR,
+                        "failed to delete the post",
+                    ))
+                }
+            }
+        }
+        _ => Err(Fault::new(
+            FAULT_METHOD_NOT_FOUND,
+            format!("method {} is not supported", method_name),
+        )),
+    }
+}
+
+pub async fn handler_xmlrpc(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    let (method_name, params) = match parse_method_call(&body) {
+        Ok(method_call) => method_call,
+        Err(err) => return Fault::new(FAULT_PARSE_ERROR, err).into_response(),
+    };
+    info!("handling XML-RPC method {}", method_name);
+    match dispatch(&state, &method_name, &params).await {
+        Ok(value) => value.into_response(),
+        Err(fault) => fault.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method_call() {
+        let body = r#"<?xml version="1.0"?>
+<methodCall>
+    <methodName>metaWeblog.newPost</methodName>
+    <params>
+        <param><value><string>1</string></value></param>
+        <param><value>admin</value></param>
+        <param><value><string>p&amp;ss</string></value></param>
+        <param><value><struct>
+            <member><name>title</name><value><string>Hello</string></value></member>
+            <member><name>description</name><value><string><![CDATA[**World**]]></string></value></member>
+            <member><name>categories</name><value><array><data>
+                <value><string>foo</string></value>
+                <value>bar</value>
+            </data></array></value></member>
+        </struct></value></param>
+        <param><value><boolean>1</boolean></value></param>
+    </params>
+</methodCall>"#;
+        let (method_name, params) = parse_method_call(body.as_bytes()).unwrap();
+        assert_eq!(method_name, "metaWeblog.newPost");
+        assert_eq!(params.len(), 5);
+        assert_eq!(params[1], XmlRpcValue::String("admin".to_string()));
+        assert_eq!(params[2], XmlRpcValue::String("p&ss".to_string()));
+        assert_eq!(params[4], XmlRpcValue::Bool(true));
+
+        let form = post_to_form(None, &params[3]);
+        assert_eq!(form.title.as_deref(), Some("Hello"));
+        assert_eq!(form.content.as_deref(), Some("**World**"));
+        assert_eq!(form.tags.as_deref(), Some("foo,bar"));
+
+        // the absent members are left as `None`.
+        let form = post_to_form(Some(1), &XmlRpcValue::Struct(vec![]));
+        assert_eq!(form.title, None);
+        assert_eq!(form.tags, None);
+        assert_eq!(form.content, None);
+    }
+
+    #[test]
+    fn test_parse_nested_value() {
+        let nested = |depth: usize| {
+            format!(
+                "<methodCall><methodName>m</methodName><params><param>{}{}</param></params></methodCall>",
+                "<value><array><data>".repeat(depth),
+                "</data></array></value>".repeat(depth)
+            )
+        };
+        assert!(parse_method_call(nested(8).as_bytes()).is_ok());
+        assert!(parse_method_call(nested(MAX_DEPTH).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_write_value() {
+        let mut out = String::new();
+        XmlRpcValue::Struct(vec![
+            ("postid".to_string(), XmlRpcValue::String("<1>".to_string())),
+            ("ok".to_string(), XmlRpcValue::Bool(true)),
+        ])
+        .write(&mut out);
+        assert_eq!(
+            out,
+            "<value><struct><member><name>postid</name><value><string>&lt;1&gt;</string></value></member>\
+             <member><name>ok</name><value><boolean>1</boolean></value></member></struct></value>"
+        );
+        assert_eq!(
+            parse_iso8601("20240102T03:04:05"),
+            Some("2024-01-02T03:04:05Z".parse().unwrap())
+        );
+    }
+}